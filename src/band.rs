@@ -0,0 +1,129 @@
+use crate::config::RamPlane;
+
+/// Horizontal strip of the screen, drawn in a small buffer and sent to the RAM before drawing the next one.
+///
+/// Coordinates are the ones of the whole screen, pixels outside of the strip are ignored.
+/// This allows rendering a full screen with only a few rows of memory, the drawing being run once per strip.
+pub struct Band<'a> {
+    buffer: &'a mut [u8],
+    width: u16,
+    screen_height: u16,
+    y: u16,
+    rows: u16,
+    plane: RamPlane,
+}
+
+impl<'a> Band<'a> {
+    /// The number of rows of a strip is the number of full rows fitting in `buffer`.
+    ///
+    /// Panics if `buffer` is smaller than one row, `width.div_ceil(8)` bytes.
+    pub fn new(buffer: &'a mut [u8], width: u16, screen_height: u16, plane: RamPlane) -> Self {
+        let stride = width.div_ceil(8) as usize;
+        assert!(
+            buffer.len() >= stride,
+            "band buffer is smaller than one row"
+        );
+        let rows = (buffer.len() / stride).min(screen_height as usize) as u16;
+        Band {
+            buffer,
+            width,
+            screen_height,
+            y: 0,
+            rows,
+            plane,
+        }
+    }
+
+    fn stride(&self) -> usize {
+        self.width.div_ceil(8) as usize
+    }
+
+    /// First row of the strip.
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    /// Number of rows of the strip, the last strip of the screen can be shorter.
+    pub fn rows(&self) -> u16 {
+        self.rows.min(self.screen_height - self.y)
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn screen_height(&self) -> u16 {
+        self.screen_height
+    }
+
+    pub fn plane(&self) -> RamPlane {
+        self.plane
+    }
+
+    /// Bytes of the strip, as written to the RAM.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.stride() * self.rows() as usize]
+    }
+
+    /// Move the strip to start at row `y` and clear it (white on the black/white plane, no red on the red plane).
+    pub fn start_at(&mut self, y: u16) {
+        self.y = y;
+        let background = match self.plane {
+            RamPlane::BlackWhite => 0xFF,
+            RamPlane::Red => 0x00,
+        };
+        self.buffer.fill(background);
+    }
+
+    /// Set the RAM bit of a pixel: on the black/white plane true is white, on the red plane true is red.
+    pub fn set_pixel(&mut self, x: u16, y: u16, value: bool) {
+        if x >= self.width || y < self.y || y >= self.y + self.rows() {
+            return;
+        }
+        let index = (y - self.y) as usize * self.stride() + x as usize / 8;
+        let mask = 0x80 >> (x % 8);
+        if value {
+            self.buffer[index] |= mask;
+        } else {
+            self.buffer[index] &= !mask;
+        }
+    }
+}
+
+#[cfg(feature = "graphics")]
+mod graphics {
+    use super::Band;
+    use crate::config::RamPlane;
+    use embedded_graphics_core::{
+        Pixel,
+        draw_target::DrawTarget,
+        geometry::{OriginDimensions, Size},
+        pixelcolor::BinaryColor,
+    };
+
+    /// On the black/white plane `BinaryColor::On` is black, on the red plane it is red.
+    impl DrawTarget for Band<'_> {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            let red = self.plane == RamPlane::Red;
+            for Pixel(point, color) in pixels {
+                if let (Ok(x), Ok(y)) = (u16::try_from(point.x), u16::try_from(point.y)) {
+                    self.set_pixel(x, y, color.is_on() == red);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// The size of the whole screen, so drawing code does not depend on the strip.
+    impl OriginDimensions for Band<'_> {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, self.screen_height as u32)
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Several displays tiled as one canvas.
+//!
+//! Each panel keeps its own frame buffer, in the layout of its RAM. The canvas maps its coordinates to the
+//! panels, according to their position and orientation, and flushes every panel its own part.
+
+use core::{convert::Infallible, marker::PhantomData};
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorType, InputPin, OutputPin},
+};
+#[cfg(feature = "async")]
+use embedded_hal_async::delay::DelayNs as AsyncDelayNs;
+
+use crate::{
+    SSD1680, error::Error, frame_check::UpdateStatus, framebuffer::FrameBuffer,
+    interface::Interface, refresh_policy::RefreshKind,
+};
+#[cfg(feature = "async")]
+use crate::{
+    driver::{AsyncBusyPin, SSD1680Async},
+    interface::AsyncInterface,
+};
+
+/// Output pin doing nothing, the RST pin of displays reset through a shared line.
+///
+/// `E` is the error type of the shared pin, never returned.
+pub struct NoPin<E = Infallible>(PhantomData<E>);
+
+impl<E> NoPin<E> {
+    pub fn new() -> Self {
+        NoPin(PhantomData)
+    }
+}
+
+impl<E> Default for NoPin<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: embedded_hal::digital::Error> ErrorType for NoPin<E> {
+    type Error = E;
+}
+
+impl<E: embedded_hal::digital::Error> OutputPin for NoPin<E> {
+    fn set_low(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Rotation of a panel in the canvas, clockwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Orientation {
+    /// Size in the canvas of a panel of `width` x `height` pixels.
+    fn extent(self, width: u16, height: u16) -> (u16, u16) {
+        match self {
+            Orientation::Rotate0 | Orientation::Rotate180 => (width, height),
+            Orientation::Rotate90 | Orientation::Rotate270 => (height, width),
+        }
+    }
+
+    /// Panel pixel shown at `(u, v)`, relative to the top left corner of the panel in the canvas.
+    fn to_panel(self, u: u16, v: u16, width: u16, height: u16) -> (u16, u16) {
+        match self {
+            Orientation::Rotate0 => (u, v),
+            Orientation::Rotate90 => (v, height - 1 - u),
+            Orientation::Rotate180 => (width - 1 - u, height - 1 - v),
+            Orientation::Rotate270 => (width - 1 - v, u),
+        }
+    }
+}
+
+/// A display of a [`Canvas`], with its frame buffer and its place in the canvas.
+///
+/// `N` is the size of the frame buffer of the display, see [`FrameBuffer`].
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "PanelAsync"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+    )
+)]
+pub struct Panel<RST, BUSY, DELAY, DI, const N: usize>
+where
+    RST: OutputPin,
+    BUSY: AsyncBusyPin,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface,
+{
+    display: SSD1680<RST, BUSY, DELAY, DI>,
+    framebuffer: FrameBuffer<N>,
+    x: u16,
+    y: u16,
+    orientation: Orientation,
+}
+
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "PanelAsync"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+    )
+)]
+impl<RST, BUSY, DELAY, DI, const N: usize> Panel<RST, BUSY, DELAY, DI, N>
+where
+    RST: OutputPin,
+    BUSY: AsyncBusyPin,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface,
+{
+    /// Place a display with its top left corner at `(x, y)` in the canvas, after rotating it.
+    pub fn new(
+        display: SSD1680<RST, BUSY, DELAY, DI>,
+        x: u16,
+        y: u16,
+        orientation: Orientation,
+    ) -> Self {
+        let config = display.config();
+        Panel {
+            framebuffer: FrameBuffer::new(config.width, config.height),
+            display,
+            x,
+            y,
+            orientation,
+        }
+    }
+
+    pub fn display(&mut self) -> &mut SSD1680<RST, BUSY, DELAY, DI> {
+        &mut self.display
+    }
+
+    /// Frame buffer of the panel, in the layout of its RAM.
+    pub fn framebuffer(&mut self) -> &mut FrameBuffer<N> {
+        &mut self.framebuffer
+    }
+
+    pub fn into_display(self) -> SSD1680<RST, BUSY, DELAY, DI> {
+        self.display
+    }
+
+    /// Canvas position of the right and bottom edges of the panel.
+    fn end(&self) -> (u16, u16) {
+        let (width, height) = self
+            .orientation
+            .extent(self.framebuffer.width(), self.framebuffer.height());
+        (self.x + width, self.y + height)
+    }
+
+    /// Panel pixel at the canvas position `(x, y)`, if the panel covers it.
+    fn locate(&self, x: u16, y: u16) -> Option<(u16, u16)> {
+        let (end_x, end_y) = self.end();
+        if x < self.x || y < self.y || x >= end_x || y >= end_y {
+            return None;
+        }
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        Some(
+            self.orientation
+                .to_panel(x - self.x, y - self.y, width, height),
+        )
+    }
+}
+
+/// `P` displays shown as a single canvas.
+///
+/// Flushing writes every panel and starts all the refreshes before waiting for them, so the panels refresh
+/// at the same time and a flush takes about as long as a single refresh.
+/// `SRST` is the reset line shared by the displays, if any (see `with_shared_reset`).
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "CanvasAsync"),
+    idents(
+        Panel(sync = "Panel", async = "PanelAsync"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+    )
+)]
+pub struct Canvas<RST, BUSY, DELAY, DI, SRST, const N: usize, const P: usize>
+where
+    RST: OutputPin,
+    BUSY: AsyncBusyPin,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface,
+    SRST: OutputPin,
+{
+    panels: [Panel<RST, BUSY, DELAY, DI, N>; P],
+    shared_reset: Option<SRST>,
+    width: u16,
+    height: u16,
+}
+
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "CanvasAsync"),
+    idents(
+        Panel(sync = "Panel", async = "PanelAsync"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+    )
+)]
+impl<RST, BUSY, DELAY, DI, R, const N: usize, const P: usize>
+    Canvas<RST, BUSY, DELAY, DI, NoPin<R>, N, P>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: AsyncBusyPin,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface,
+    R: embedded_hal::digital::Error,
+{
+    /// The canvas extends to the right and bottom edges of the furthest panels.
+    ///
+    /// Each display is reset with its own RST pin.
+    pub fn new(panels: [Panel<RST, BUSY, DELAY, DI, N>; P]) -> Self {
+        Canvas::with_shared_reset(panels, None)
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "CanvasAsync"),
+    idents(
+        Panel(sync = "Panel", async = "PanelAsync"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+    )
+)]
+impl<RST, BUSY, DELAY, DI, SRST, S, R, D, B, const N: usize, const P: usize>
+    Canvas<RST, BUSY, DELAY, DI, SRST, N, P>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: AsyncBusyPin<Error = B>,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface<BusError = S, PinError = D>,
+    SRST: OutputPin<Error = R>,
+{
+    /// Displays whose reset inputs are wired together to `shared_reset`, it is pulsed once by `hw_init`.
+    ///
+    /// Give the drivers a [`NoPin`] as RST pin, with the error type of `shared_reset`.
+    pub fn with_shared_reset(
+        panels: [Panel<RST, BUSY, DELAY, DI, N>; P],
+        shared_reset: Option<SRST>,
+    ) -> Self {
+        let (width, height) = panels.iter().fold((0, 0), |(width, height), panel| {
+            let (end_x, end_y) = panel.end();
+            (width.max(end_x), height.max(end_y))
+        });
+        Canvas {
+            panels,
+            shared_reset,
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn panels(&mut self) -> &mut [Panel<RST, BUSY, DELAY, DI, N>; P] {
+        &mut self.panels
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn release(self) -> ([Panel<RST, BUSY, DELAY, DI, N>; P], Option<SRST>) {
+        (self.panels, self.shared_reset)
+    }
+
+    /// Get a pixel, true is white. `None` outside of the panels.
+    pub fn pixel(&self, x: u16, y: u16) -> Option<bool> {
+        self.panels.iter().find_map(|panel| {
+            let (x, y) = panel.locate(x, y)?;
+            panel.framebuffer.pixel(x, y)
+        })
+    }
+
+    /// Set a pixel on every panel covering it, true is white.
+    pub fn set_pixel(&mut self, x: u16, y: u16, white: bool) {
+        for panel in self.panels.iter_mut() {
+            if let Some((x, y)) = panel.locate(x, y) {
+                panel.framebuffer.set_pixel(x, y, white);
+            }
+        }
+    }
+
+    /// Fill every panel with a single color, true is white.
+    pub fn fill(&mut self, white: bool) {
+        for panel in self.panels.iter_mut() {
+            panel.framebuffer.fill(white);
+        }
+    }
+
+    /// Reset and initialize all the displays.
+    ///
+    /// The shared reset line is pulsed once, otherwise each display pulses its RST pin. All the displays are
+    /// reset before the first one is initialized.
+    pub async fn hw_init(&mut self) -> Result<(), Error<S, R, D, B>> {
+        if let Some(shared_reset) = self.shared_reset.as_mut() {
+            if let Some((first, others)) = self.panels.split_first_mut() {
+                first.display.hw_reset_with(shared_reset).await?;
+                for panel in others {
+                    panel.display.assume_reset()?;
+                }
+            }
+        } else {
+            for panel in self.panels.iter_mut() {
+                panel.display.hw_reset().await?;
+            }
+        }
+        for panel in self.panels.iter_mut() {
+            panel.display.hw_init_after_reset().await?;
+        }
+        Ok(())
+    }
+
+    /// Send the changes of every panel and refresh them together.
+    ///
+    /// The refreshes are all started before waiting for the BUSY lines. Panels whose content is already
+    /// displayed are left alone, unless `force` is true.
+    pub async fn flush(
+        &mut self,
+        kind: RefreshKind,
+        force: bool,
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        let mut started = [false; P];
+        for (panel, started) in self.panels.iter_mut().zip(started.iter_mut()) {
+            let status = panel
+                .display
+                .start_framebuffer(&mut panel.framebuffer, kind, force)
+                .await?;
+            *started = status == UpdateStatus::Updated;
+        }
+
+        let mut status = UpdateStatus::Unchanged;
+        for (panel, started) in self.panels.iter_mut().zip(started) {
+            if started {
+                panel
+                    .display
+                    .finish_framebuffer(&mut panel.framebuffer, kind)
+                    .await?;
+                status = UpdateStatus::Updated;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Put every display into deep sleep.
+    pub async fn enter_deep_sleep(&mut self) -> Result<(), Error<S, R, D, B>> {
+        for panel in self.panels.iter_mut() {
+            panel.display.enter_deep_sleep().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+mod graphics {
+    use embedded_graphics_core::{
+        Pixel,
+        draw_target::DrawTarget,
+        geometry::{OriginDimensions, Size},
+        pixelcolor::BinaryColor,
+    };
+    use embedded_hal::{
+        delay::DelayNs,
+        digital::{InputPin, OutputPin},
+    };
+    #[cfg(feature = "async")]
+    use embedded_hal_async::delay::DelayNs as AsyncDelayNs;
+
+    use super::Canvas;
+    #[cfg(feature = "async")]
+    use super::CanvasAsync;
+    use crate::interface::Interface;
+    #[cfg(feature = "async")]
+    use crate::{driver::AsyncBusyPin, interface::AsyncInterface};
+
+    /// `BinaryColor::On` is black, `BinaryColor::Off` is white.
+    #[maybe_async_cfg::maybe(
+        sync(keep_self),
+        async(feature = "async", self = "CanvasAsync"),
+        idents(
+            AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+            AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+            AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+        )
+    )]
+    impl<RST, BUSY, DELAY, DI, SRST, const N: usize, const P: usize> DrawTarget
+        for Canvas<RST, BUSY, DELAY, DI, SRST, N, P>
+    where
+        RST: OutputPin,
+        BUSY: AsyncBusyPin,
+        DELAY: AsyncDelayNs,
+        DI: AsyncInterface,
+        SRST: OutputPin<Error = RST::Error>,
+    {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                if let (Ok(x), Ok(y)) = (u16::try_from(point.x), u16::try_from(point.y)) {
+                    self.set_pixel(x, y, color.is_off());
+                }
+            }
+            Ok(())
+        }
+
+        fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+            self.fill(color.is_off());
+            Ok(())
+        }
+    }
+
+    #[maybe_async_cfg::maybe(
+        sync(keep_self),
+        async(feature = "async", self = "CanvasAsync"),
+        idents(
+            AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+            AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+            AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+        )
+    )]
+    impl<RST, BUSY, DELAY, DI, SRST, const N: usize, const P: usize> OriginDimensions
+        for Canvas<RST, BUSY, DELAY, DI, SRST, N, P>
+    where
+        RST: OutputPin,
+        BUSY: AsyncBusyPin,
+        DELAY: AsyncDelayNs,
+        DI: AsyncInterface,
+        SRST: OutputPin,
+    {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, self.height as u32)
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub enum SsdCommand {
     DriveOutputControl = 0x01,
     GateDrivingVoltageControl = 0x03,
@@ -49,6 +50,30 @@ pub enum SsdCommand {
     SetRamYCounter = 0x4F,
     Nop = 0x7F,
 }
+impl SsdCommand {
+    /// True for the commands starting an operation during which the BUSY pin is high.
+    pub fn sets_busy(&self) -> bool {
+        matches!(
+            self,
+            SsdCommand::InitialCodeSettingOTPProgram
+                | SsdCommand::DeepSleepMode
+                | SsdCommand::SWReset
+                | SsdCommand::HVReadyDetection
+                | SsdCommand::VCIDetection
+                | SsdCommand::MasterActivation
+                | SsdCommand::VcomSense
+                | SsdCommand::ProgramVcomOtp
+                | SsdCommand::ProgramWsOtp
+                | SsdCommand::LoadWsOtp
+                | SsdCommand::CrcCalculation
+                | SsdCommand::ProgramOtpSelection
+                | SsdCommand::OtpProgramMode
+                | SsdCommand::AutoWriteRedRam
+                | SsdCommand::AutoWriteBWRam
+        )
+    }
+}
+
 impl From<SsdCommand> for u8 {
     fn from(val: SsdCommand) -> Self {
         val as u8
@@ -1,3 +1,5 @@
+use crate::commands::SsdCommand;
+
 #[derive(Clone, Copy)]
 pub enum LUTSelect {
     LUT0 = 0x00,
@@ -58,6 +60,146 @@ impl From<UpdateRamOption> for u8 {
     }
 }
 
+/// Auto write RAM (commands 0x46 and 0x47), bits 4 to 6: height of a step, in gates
+#[derive(Clone, Copy)]
+pub enum AutoWriteStepHeight {
+    H8 = 0x00,
+    H16 = 0x10,
+    H32 = 0x20,
+    H64 = 0x30,
+    H128 = 0x40,
+    H256 = 0x50,
+    H296 = 0x60,
+}
+
+/// Auto write RAM (commands 0x46 and 0x47), bits 0 to 2: width of a step, in sources
+#[derive(Clone, Copy)]
+pub enum AutoWriteStepWidth {
+    W8 = 0x00,
+    W16 = 0x01,
+    W32 = 0x02,
+    W64 = 0x03,
+    W128 = 0x04,
+    W176 = 0x05,
+}
+
+/// Pattern generated by the controller with the auto write RAM commands.
+///
+/// The RAM is split in steps, the color alternates from one step to the next, starting with `first_step_color`.
+/// Steps as large as the RAM give a solid fill, small steps give stripes or a checkerboard.
+#[derive(Clone, Copy)]
+pub struct AutoWritePattern {
+    pub first_step_color: bool,
+    pub step_height: AutoWriteStepHeight,
+    pub step_width: AutoWriteStepWidth,
+}
+
+impl AutoWritePattern {
+    pub fn new(
+        first_step_color: bool,
+        step_height: AutoWriteStepHeight,
+        step_width: AutoWriteStepWidth,
+    ) -> Self {
+        AutoWritePattern {
+            first_step_color,
+            step_height,
+            step_width,
+        }
+    }
+
+    /// A single color, the steps covering the whole RAM.
+    pub fn solid(color: bool) -> Self {
+        Self::new(color, AutoWriteStepHeight::H296, AutoWriteStepWidth::W176)
+    }
+
+    pub fn into_u8(&self) -> u8 {
+        ((self.first_step_color as u8) << 7) | self.step_height as u8 | self.step_width as u8
+    }
+}
+
+/// RAM plane of the controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamPlane {
+    BlackWhite,
+    Red,
+}
+
+impl RamPlane {
+    /// Command writing to this plane
+    pub fn write_command(&self) -> SsdCommand {
+        match self {
+            RamPlane::BlackWhite => SsdCommand::WriteRamBW,
+            RamPlane::Red => SsdCommand::WriteRamRed,
+        }
+    }
+}
+
+/// How partial refreshes use the red RAM
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PartialRefreshMode {
+    /// The red RAM is left untouched, use it on three colors panels.
+    Standalone,
+    /// The red RAM holds the frame currently displayed, the partial refresh waveform compares it with the new frame in the black/white RAM.
+    /// Only for black and white panels.
+    PreviousFrameInRed,
+    /// RAM ping-pong of Display Mode 2, the controller itself keeps the previous frame after each partial refresh.
+    /// The planes swap after each of them, so the regions of an update are written again with the next one.
+    /// The ping-pong bit of the display option register is set by `hw_init`.
+    PingPong,
+}
+
+/// Display option register (command 0x37)
+#[derive(Clone, Copy, Default)]
+pub struct DisplayOption {
+    /// Use the spare VCOM OTP
+    pub spare_vcom_otp: bool,
+    /// One bit per waveform setting (WS0 to WS35), set to use Display Mode 2 instead of Display Mode 1
+    pub display_mode_2: u64,
+    /// RAM ping-pong for Display Mode 2, the black/white and red RAM swap roles after each update
+    pub ping_pong: bool,
+    /// Module ID / waveform version
+    pub module_id: [u8; 4],
+}
+
+impl DisplayOption {
+    pub fn with_spare_vcom_otp(mut self, spare: bool) -> Self {
+        self.spare_vcom_otp = spare;
+        self
+    }
+
+    pub fn with_display_mode_2(mut self, waveform_settings: u64) -> Self {
+        self.display_mode_2 = waveform_settings;
+        self
+    }
+
+    pub fn with_ping_pong(mut self, ping_pong: bool) -> Self {
+        self.ping_pong = ping_pong;
+        self
+    }
+
+    pub fn with_module_id(mut self, module_id: [u8; 4]) -> Self {
+        self.module_id = module_id;
+        self
+    }
+
+    /// Parameters of the command 0x37
+    pub fn into_bytes(&self) -> [u8; 10] {
+        let ws = self.display_mode_2;
+        [
+            (self.spare_vcom_otp as u8) << 7,
+            ws as u8,
+            (ws >> 8) as u8,
+            (ws >> 16) as u8,
+            (ws >> 24) as u8,
+            ((self.ping_pong as u8) << 6) | ((ws >> 32) as u8 & 0x0F),
+            self.module_id[0],
+            self.module_id[1],
+            self.module_id[2],
+            self.module_id[3],
+        ]
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct DisplayConfig {
     pub width: u16,
@@ -74,11 +216,20 @@ pub struct DisplayConfig {
 
     pub border_waveform_control: VDBMode,
 
-    // display update control 1
-    pub ram_content_for_display_update: UpdateRamOption,
+    // display update control 1, black/white then red plane
+    pub bw_ram_content_for_display_update: UpdateRamOption,
+    pub red_ram_content_for_display_update: UpdateRamOption,
     pub s8_source_output_mode: bool,
 
     pub use_internal_temperature_sensor: bool,
+
+    pub partial_refresh_mode: PartialRefreshMode,
+
+    // display option register (command 0x37), left to its reset value when None
+    pub display_option: Option<DisplayOption>,
+
+    // maximum size of a single SPI write, some HALs and DMA engines limit the length of a transfer
+    pub max_transfer_size: usize,
     // TODO: handle rotation
 }
 
@@ -94,9 +245,13 @@ impl Default for DisplayConfig {
             partial_refresh_sequence: 0xFC,
             full_refresh_sequence: 0xF7,
             border_waveform_control: VDBMode::GSTransition(true, LUTSelect::LUT1),
-            ram_content_for_display_update: UpdateRamOption::Normal,
+            bw_ram_content_for_display_update: UpdateRamOption::Normal,
+            red_ram_content_for_display_update: UpdateRamOption::Normal,
             s8_source_output_mode: true,
             use_internal_temperature_sensor: true,
+            partial_refresh_mode: PartialRefreshMode::Standalone,
+            display_option: None,
+            max_transfer_size: usize::MAX,
         }
     }
 }
@@ -139,18 +294,41 @@ impl DisplayConfig {
         self
     }
 
+    /// Option of both planes, and the source output mode.
     pub fn with_ram_content_for_display_update(
         mut self,
         option: UpdateRamOption,
         s8_source_output_mode: bool,
     ) -> Self {
-        self.ram_content_for_display_update = option;
+        self.bw_ram_content_for_display_update = option;
+        self.red_ram_content_for_display_update = option;
         self.s8_source_output_mode = s8_source_output_mode;
         self
     }
 
+    /// Option of the red plane only, `with_ram_content_for_display_update` sets both planes.
+    pub fn with_red_ram_content_for_display_update(mut self, option: UpdateRamOption) -> Self {
+        self.red_ram_content_for_display_update = option;
+        self
+    }
+
     pub fn with_use_internal_temperature_sensor(mut self, internal: bool) -> Self {
         self.use_internal_temperature_sensor = internal;
         self
     }
+
+    pub fn with_partial_refresh_mode(mut self, mode: PartialRefreshMode) -> Self {
+        self.partial_refresh_mode = mode;
+        self
+    }
+
+    pub fn with_display_option(mut self, option: Option<DisplayOption>) -> Self {
+        self.display_option = option;
+        self
+    }
+
+    pub fn with_max_transfer_size(mut self, size: usize) -> Self {
+        self.max_transfer_size = size.max(1);
+        self
+    }
 }
@@ -0,0 +1,348 @@
+use core::ops::Range;
+
+use embedded_graphics_core::{
+    Pixel,
+    draw_target::DrawTarget,
+    geometry::{Dimensions, OriginDimensions, Size},
+    pixelcolor::BinaryColor,
+    primitives::{PointsIter, Rectangle},
+};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use crate::{SSD1680, config::RamPlane, error::Error, interface::Interface};
+
+/// Size of the buffer used to stream pixels to the controller.
+const SCRATCH_SIZE: usize = 32;
+
+/// Draw target writing straight into the controller RAM, without frame buffer.
+///
+/// Whole bytes are written through RAM windows, pixels sharing a byte with pixels outside of the drawn area are
+/// read back from the RAM and written again (read-modify-write).
+/// On the black/white plane `BinaryColor::On` is black, on the red plane it is red.
+pub struct DirectDraw<'a, RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin,
+    BUSY: InputPin,
+    DELAY: DelayNs,
+    DI: Interface,
+{
+    display: &'a mut SSD1680<RST, BUSY, DELAY, DI>,
+    plane: RamPlane,
+    /// Byte being modified by `draw_iter`: x in bytes, y, value
+    cache: Option<(u16, u16, u8)>,
+}
+
+impl<RST, BUSY, DELAY, DI, S, R, D, B> SSD1680<RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: InputPin<Error = B>,
+    DELAY: DelayNs,
+    DI: Interface<BusError = S, PinError = D>,
+{
+    /// Draw directly into one of the RAM planes, the screen is only updated by a refresh.
+    pub fn direct_draw(&mut self, plane: RamPlane) -> DirectDraw<'_, RST, BUSY, DELAY, DI> {
+        DirectDraw {
+            display: self,
+            plane,
+            cache: None,
+        }
+    }
+}
+
+impl<RST, BUSY, DELAY, DI, S, R, D, B> DirectDraw<'_, RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: InputPin<Error = B>,
+    DELAY: DelayNs,
+    DI: Interface<BusError = S, PinError = D>,
+{
+    /// Value of the RAM bit for a color.
+    fn bit(&self, color: BinaryColor) -> bool {
+        color.is_on() == (self.plane == RamPlane::Red)
+    }
+
+    /// Write bytes from the current RAM counters, with a single RAM write command.
+    fn write<I: IntoIterator<Item = u8>>(&mut self, bytes: I) -> Result<(), Error<S, R, D, B>> {
+        self.display.write_ram_iter(self.plane, bytes)
+    }
+
+    /// Read-modify-write the bits of `mask` in a column of bytes starting at `(x, y)` (`x` in pixels, multiple
+    /// of 8), with the bits of `values`, one byte per row. At most `SCRATCH_SIZE` rows, the RAM window is left set.
+    fn modify_column(
+        &mut self,
+        x: u16,
+        y: u16,
+        mask: u8,
+        values: &[u8],
+    ) -> Result<(), Error<S, R, D, B>> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        let mut scratch = [0u8; SCRATCH_SIZE];
+        let bytes = &mut scratch[..values.len()];
+        let rows = values.len() as u16;
+        self.display
+            .read_ram_region(self.plane, x, y, 8, rows, bytes)?;
+        for (byte, value) in bytes.iter_mut().zip(values) {
+            *byte = (*byte & !mask) | (value & mask);
+        }
+        self.display.set_ram_window(x, y, 8, rows)?;
+        self.write(bytes.iter().copied())
+    }
+
+    /// Set the bits of `mask` to `bit` in a column of bytes, see `modify_column`.
+    fn fill_column(
+        &mut self,
+        x: u16,
+        y: u16,
+        height: u16,
+        mask: u8,
+        bit: bool,
+    ) -> Result<(), Error<S, R, D, B>> {
+        let values = [if bit { 0xFF } else { 0x00 }; SCRATCH_SIZE];
+        let mut row = y;
+        while row < y + height {
+            let rows = (y + height - row).min(SCRATCH_SIZE as u16);
+            self.modify_column(x, row, mask, &values[..rows as usize])?;
+            row += rows;
+        }
+        self.display.reset_ram_window()
+    }
+
+    /// Write the byte cached by `draw_iter` back to the RAM.
+    fn flush_cache(&mut self) -> Result<(), Error<S, R, D, B>> {
+        if let Some((x, y, value)) = self.cache.take() {
+            self.display.set_ram_window(x * 8, y, 8, 1)?;
+            self.write([value])?;
+            self.display.reset_ram_window()?;
+        }
+        Ok(())
+    }
+
+    fn set_pixel(&mut self, x: u16, y: u16, bit: bool) -> Result<(), Error<S, R, D, B>> {
+        let x_byte = x / 8;
+        let value = match self.cache {
+            Some((cached_x, cached_y, value)) if cached_x == x_byte && cached_y == y => value,
+            _ => {
+                self.flush_cache()?;
+                let mut value = [0u8; 1];
+                self.display
+                    .read_ram_region(self.plane, x_byte * 8, y, 8, 1, &mut value)?;
+                value[0]
+            }
+        };
+        let mask = 0x80 >> (x % 8);
+        let value = if bit { value | mask } else { value & !mask };
+        self.cache = Some((x_byte, y, value));
+        Ok(())
+    }
+}
+
+impl<RST, BUSY, DELAY, DI, S, R, D, B> DrawTarget for DirectDraw<'_, RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: InputPin<Error = B>,
+    DELAY: DelayNs,
+    DI: Interface<BusError = S, PinError = D>,
+{
+    type Color = BinaryColor;
+    type Error = Error<S, R, D, B>;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let size = self.size();
+        for Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (u16::try_from(point.x), u16::try_from(point.y))
+                && (x as u32) < size.width
+                && (y as u32) < size.height
+            {
+                self.set_pixel(x, y, self.bit(color))?;
+            }
+        }
+        self.flush_cache()
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if area.intersection(&self.bounding_box()) != *area || area.is_zero_sized() {
+            return self.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(point, color)| Pixel(point, color)),
+            );
+        }
+        self.flush_cache()?;
+
+        let (x0, y) = (area.top_left.x as u16, area.top_left.y as u16);
+        let x1 = x0 + area.size.width as u16;
+        let height = area.size.height as u16;
+
+        // whole bytes are streamed through a RAM window, the partial bytes on the edges are read-modify-written
+        let inner = x0.next_multiple_of(8)..x1 & !7;
+        let left = x0 & !7;
+        let (mut left_mask, mut right_mask) = (0u8, 0u8);
+        for x in (x0..x1).filter(|x| !inner.contains(x)) {
+            if x & !7 == left {
+                left_mask |= 0x80 >> (x % 8);
+            } else {
+                right_mask |= 0x80 >> (x % 8);
+            }
+        }
+
+        let mut colors = colors.into_iter();
+        let mut left_bits = [0u8; SCRATCH_SIZE];
+        let mut right_bits = [0u8; SCRATCH_SIZE];
+        let mut row = y;
+        while row < y + height {
+            let rows = (y + height - row).min(SCRATCH_SIZE as u16);
+            let mut band = BandBytes {
+                colors: &mut colors,
+                red: self.plane == RamPlane::Red,
+                columns: x0..x1,
+                inner: inner.clone(),
+                left,
+                x: x0,
+                row: 0,
+                rows,
+                byte: 0,
+                left_bits: &mut left_bits,
+                right_bits: &mut right_bits,
+            };
+            if !inner.is_empty() {
+                self.display
+                    .set_ram_window(inner.start, row, inner.end - inner.start, rows)?;
+                self.display.write_ram_iter(self.plane, &mut band)?;
+            }
+            // the pixels after the last whole byte of the band
+            band.by_ref().for_each(drop);
+            let done = band.row as usize;
+
+            if left_mask != 0 {
+                self.modify_column(left, row, left_mask, &left_bits[..done])?;
+            }
+            if right_mask != 0 {
+                self.modify_column(x1 & !7, row, right_mask, &right_bits[..done])?;
+            }
+            if done < rows as usize {
+                // fewer colors than pixels
+                break;
+            }
+            row += rows;
+        }
+        self.display.reset_ram_window()
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        self.flush_cache()?;
+
+        let bit = self.bit(color);
+        let (x0, y) = (area.top_left.x as u16, area.top_left.y as u16);
+        let x1 = x0 + area.size.width as u16;
+        let height = area.size.height as u16;
+
+        // whole bytes, through a RAM window
+        let inner_start = x0.next_multiple_of(8);
+        let inner_end = x1 & !7;
+        if inner_start < inner_end {
+            self.display
+                .set_ram_window(inner_start, y, inner_end - inner_start, height)?;
+            let count = (inner_end - inner_start) as usize / 8 * height as usize;
+            self.display
+                .write_ram_repeat(self.plane, if bit { 0xFF } else { 0x00 }, count)?;
+            self.display.reset_ram_window()?;
+        }
+
+        // partial bytes on the edges, read-modify-write
+        let left = x0 & !7;
+        if !x0.is_multiple_of(8) || inner_start >= inner_end {
+            let end = x1.min(left + 8);
+            let mask =
+                (0xFFu8 >> (x0 - left)) & !(0xFFu8.checked_shr((end - left) as u32).unwrap_or(0));
+            self.fill_column(left, y, height, mask, bit)?;
+        }
+        let right = x1 & !7;
+        if !x1.is_multiple_of(8) && right != left {
+            let mask = !(0xFFu8 >> (x1 - right));
+            self.fill_column(right, y, height, mask, bit)?;
+        }
+        Ok(())
+    }
+}
+
+impl<RST, BUSY, DELAY, DI, S, R, D, B> OriginDimensions for DirectDraw<'_, RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: InputPin<Error = B>,
+    DELAY: DelayNs,
+    DI: Interface<BusError = S, PinError = D>,
+{
+    fn size(&self) -> Size {
+        let config = self.display.config();
+        Size::new(config.width as u32, config.height as u32)
+    }
+}
+
+/// Bytes of the whole-byte columns of a band of rows of `fill_contiguous`, built from the colors.
+///
+/// The bits of the partial bytes on the edges are kept aside in `left_bits` and `right_bits`, one byte per row.
+struct BandBytes<'a, I> {
+    colors: &'a mut I,
+    red: bool,
+    columns: Range<u16>,
+    inner: Range<u16>,
+    left: u16,
+    x: u16,
+    /// Rows completed
+    row: u16,
+    rows: u16,
+    byte: u8,
+    left_bits: &'a mut [u8; SCRATCH_SIZE],
+    right_bits: &'a mut [u8; SCRATCH_SIZE],
+}
+
+impl<I: Iterator<Item = BinaryColor>> Iterator for BandBytes<'_, I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.row < self.rows {
+            let bit = (self.colors.next()?.is_on() == self.red) as u8;
+            let x = self.x;
+            let mut byte = None;
+            if self.inner.contains(&x) {
+                self.byte = (self.byte << 1) | bit;
+                if x % 8 == 7 {
+                    byte = Some(self.byte);
+                }
+            } else {
+                let bits = if x & !7 == self.left {
+                    &mut self.left_bits[self.row as usize]
+                } else {
+                    &mut self.right_bits[self.row as usize]
+                };
+                let mask = 0x80 >> (x % 8);
+                *bits = (*bits & !mask) | if bit != 0 { mask } else { 0 };
+            }
+
+            self.x += 1;
+            if self.x == self.columns.end {
+                self.x = self.columns.start;
+                self.row += 1;
+            }
+            if byte.is_some() {
+                return byte;
+            }
+        }
+        None
+    }
+}
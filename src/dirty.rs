@@ -0,0 +1,224 @@
+/// Maximum number of separate regions tracked, further regions are merged together.
+pub const MAX_DIRTY_RECTS: usize = 8;
+
+/// Approximation of the bytes spent to send one more region (RAM window, counters and write command).
+const RECT_OVERHEAD: u32 = 16;
+
+/// Rectangle of the screen, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Grow the rectangle horizontally to whole RAM bytes (multiples of 8 pixels).
+    pub fn byte_aligned(&self) -> Self {
+        let start = self.x & !7;
+        let end = (self.x + self.width).next_multiple_of(8);
+        Rect::new(start, self.y, end - start, self.height)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Area in pixels.
+    pub fn area(&self) -> u32 {
+        self.width as u32 * self.height as u32
+    }
+
+    /// Smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let end_x = (self.x + self.width).max(other.x + other.width);
+        let end_y = (self.y + self.height).max(other.y + other.height);
+        Rect::new(x, y, end_x - x, end_y - y)
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+
+    /// Bytes needed to send the rectangle, overhead included.
+    fn cost(&self) -> u32 {
+        self.area() / 8 + RECT_OVERHEAD
+    }
+}
+
+/// Byte-aligned regions of the screen changed since the last flush.
+///
+/// Overlapping or close regions are merged when sending their bounding box costs fewer bytes than sending them separately.
+#[derive(Clone)]
+pub struct DirtyRegions {
+    rects: [Rect; MAX_DIRTY_RECTS],
+    len: usize,
+}
+
+impl Default for DirtyRegions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirtyRegions {
+    pub const fn new() -> Self {
+        DirtyRegions {
+            rects: [Rect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            }; MAX_DIRTY_RECTS],
+            len: 0,
+        }
+    }
+
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects[..self.len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Total area of the regions, in pixels.
+    pub fn area(&self) -> u32 {
+        self.rects().iter().map(Rect::area).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Mark a rectangle as changed.
+    pub fn add(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        let mut rect = rect.byte_aligned();
+
+        // merging can make the result worth merging with another region, so loop until stable
+        loop {
+            if self.rects().iter().any(|r| r.contains(&rect)) {
+                return;
+            }
+
+            let mergeable = self
+                .rects()
+                .iter()
+                .position(|r| r.union(&rect).cost() <= r.cost() + rect.cost());
+
+            match mergeable {
+                Some(index) => {
+                    rect = rect.union(&self.rects[index]);
+                    self.remove(index);
+                }
+                None => break,
+            }
+        }
+
+        if self.len < MAX_DIRTY_RECTS {
+            self.rects[self.len] = rect;
+            self.len += 1;
+            return;
+        }
+
+        // full, merge the new rectangle with the region that grows the least
+        let (index, _) = self
+            .rects()
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (i, r.union(&rect).cost() - r.cost()))
+            .min_by_key(|(_, extra)| *extra)
+            .unwrap_or((0, 0));
+        let merged = rect.union(&self.rects[index]);
+        self.remove(index);
+        self.add(merged);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.rects.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirtyRegions, MAX_DIRTY_RECTS, Rect};
+
+    #[test]
+    fn aligns_to_bytes() {
+        let mut regions = DirtyRegions::new();
+        regions.add(Rect::new(3, 5, 2, 1));
+        regions.add(Rect::new(10, 7, 0, 4));
+        assert_eq!(regions.rects(), &[Rect::new(0, 5, 8, 1)]);
+    }
+
+    #[test]
+    fn merges_adjacent_rows() {
+        let mut regions = DirtyRegions::new();
+        regions.add(Rect::new(0, 0, 8, 1));
+        regions.add(Rect::new(0, 1, 8, 1));
+        regions.add(Rect::new(0, 2, 8, 1));
+        assert_eq!(regions.rects(), &[Rect::new(0, 0, 8, 3)]);
+    }
+
+    #[test]
+    fn merges_chain_of_regions() {
+        let mut regions = DirtyRegions::new();
+        regions.add(Rect::new(0, 0, 8, 1));
+        regions.add(Rect::new(0, 2, 8, 1));
+        // bridges both regions, the result is merged again with the second one
+        regions.add(Rect::new(0, 1, 8, 1));
+        assert_eq!(regions.rects(), &[Rect::new(0, 0, 8, 3)]);
+    }
+
+    #[test]
+    fn keeps_distant_regions_apart() {
+        let mut regions = DirtyRegions::new();
+        regions.add(Rect::new(0, 0, 8, 1));
+        regions.add(Rect::new(120, 200, 8, 1));
+        assert_eq!(
+            regions.rects(),
+            &[Rect::new(0, 0, 8, 1), Rect::new(120, 200, 8, 1)]
+        );
+    }
+
+    #[test]
+    fn ignores_contained_region() {
+        let mut regions = DirtyRegions::new();
+        regions.add(Rect::new(0, 0, 64, 10));
+        regions.add(Rect::new(8, 2, 8, 1));
+        assert_eq!(regions.rects(), &[Rect::new(0, 0, 64, 10)]);
+    }
+
+    #[test]
+    fn merges_when_full() {
+        let mut regions = DirtyRegions::new();
+        let added: [Rect; MAX_DIRTY_RECTS + 1] =
+            core::array::from_fn(|i| Rect::new(i as u16 * 32, i as u16 * 32, 8, 1));
+        for rect in added {
+            regions.add(rect);
+        }
+        assert_eq!(regions.rects().len(), MAX_DIRTY_RECTS);
+        for rect in added {
+            assert!(regions.rects().iter().any(|r| r.contains(&rect)));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use crate::{SSD1680, config::DisplayConfig, error::Error, interface::Interface};
+#[cfg(feature = "async")]
+use crate::{
+    driver::{AsyncBusyPin, SSD1680Async},
+    interface::AsyncInterface,
+};
+#[cfg(feature = "async")]
+use embedded_hal_async::delay::DelayNs as AsyncDelayNs;
+
+/// Operations common to the SSD1680 drivers, for code that should not depend on the pins and interface types.
+///
+/// `DisplayDriver` is implemented by the blocking driver, `AsyncDisplayDriver` by the async one.
+#[maybe_async_cfg::maybe(
+    sync(self = "DisplayDriver"),
+    async(feature = "async", keep_self),
+    idents(AsyncDisplayDriver(sync = "DisplayDriver", async = "AsyncDisplayDriver"))
+)]
+#[allow(async_fn_in_trait)]
+pub trait AsyncDisplayDriver {
+    type Error;
+
+    fn config(&self) -> &DisplayConfig;
+
+    /// Reset and initialize the controller.
+    async fn hw_init(&mut self) -> Result<(), Self::Error>;
+
+    /// Restrict RAM writes to a region, `x` and `width` in pixels (`x` a multiple of 8, `width` rounded up to bytes).
+    async fn set_ram_window(
+        &mut self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+    ) -> Result<(), Self::Error>;
+
+    /// Set the RAM window back to the whole screen.
+    async fn reset_ram_window(&mut self) -> Result<(), Self::Error>;
+
+    async fn set_ram_counter_x(&mut self, x: u16) -> Result<(), Self::Error>;
+
+    async fn set_ram_counter_y(&mut self, y: u16) -> Result<(), Self::Error>;
+
+    async fn write_bw_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    async fn write_red_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    async fn refresh_screen_custom_sequence(&mut self, sequence: u8) -> Result<(), Self::Error>;
+
+    async fn full_refresh(&mut self) -> Result<(), Self::Error>;
+
+    async fn partial_refresh(&mut self) -> Result<(), Self::Error>;
+
+    async fn enter_deep_sleep(&mut self) -> Result<(), Self::Error>;
+}
+
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncDisplayDriver(sync = "DisplayDriver", async = "AsyncDisplayDriver"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs")
+    )
+)]
+impl<RST, BUSY, DELAY, DI, S, R, D, B> AsyncDisplayDriver for SSD1680<RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: AsyncBusyPin<Error = B>,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface<BusError = S, PinError = D>,
+{
+    type Error = Error<S, R, D, B>;
+
+    fn config(&self) -> &DisplayConfig {
+        SSD1680::config(self)
+    }
+
+    async fn hw_init(&mut self) -> Result<(), Self::Error> {
+        SSD1680::hw_init(self).await
+    }
+
+    async fn set_ram_window(
+        &mut self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+    ) -> Result<(), Self::Error> {
+        SSD1680::set_ram_window(self, x, y, width, height).await
+    }
+
+    async fn reset_ram_window(&mut self) -> Result<(), Self::Error> {
+        SSD1680::reset_ram_window(self).await
+    }
+
+    async fn set_ram_counter_x(&mut self, x: u16) -> Result<(), Self::Error> {
+        SSD1680::set_ram_counter_x(self, x).await
+    }
+
+    async fn set_ram_counter_y(&mut self, y: u16) -> Result<(), Self::Error> {
+        SSD1680::set_ram_counter_y(self, y).await
+    }
+
+    async fn write_bw_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        SSD1680::write_bw_bytes(self, data).await
+    }
+
+    async fn write_red_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        SSD1680::write_red_bytes(self, data).await
+    }
+
+    async fn refresh_screen_custom_sequence(&mut self, sequence: u8) -> Result<(), Self::Error> {
+        SSD1680::refresh_screen_custom_sequence(self, sequence).await
+    }
+
+    async fn full_refresh(&mut self) -> Result<(), Self::Error> {
+        SSD1680::full_refresh(self).await
+    }
+
+    async fn partial_refresh(&mut self) -> Result<(), Self::Error> {
+        SSD1680::partial_refresh(self).await
+    }
+
+    async fn enter_deep_sleep(&mut self) -> Result<(), Self::Error> {
+        SSD1680::enter_deep_sleep(self).await
+    }
+}
+
+#[cfg(feature = "async")]
+pub use blocking::{BlockingDisplay, block_on};
+
+#[cfg(feature = "async")]
+mod blocking {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::{AsyncDisplayDriver, DisplayDriver};
+    use crate::config::DisplayConfig;
+
+    /// Run a future to completion by polling it in a loop.
+    ///
+    /// Nothing sleeps between two polls, this is meant for tests and for HALs whose futures complete without
+    /// waiting on an interrupt.
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// Blocking [`DisplayDriver`] running an async driver with [`block_on`].
+    pub struct BlockingDisplay<D> {
+        display: D,
+    }
+
+    impl<D: AsyncDisplayDriver> BlockingDisplay<D> {
+        pub fn new(display: D) -> Self {
+            BlockingDisplay { display }
+        }
+
+        pub fn inner(&mut self) -> &mut D {
+            &mut self.display
+        }
+
+        pub fn into_inner(self) -> D {
+            self.display
+        }
+    }
+
+    impl<D: AsyncDisplayDriver> DisplayDriver for BlockingDisplay<D> {
+        type Error = D::Error;
+
+        fn config(&self) -> &DisplayConfig {
+            self.display.config()
+        }
+
+        fn hw_init(&mut self) -> Result<(), Self::Error> {
+            block_on(self.display.hw_init())
+        }
+
+        fn set_ram_window(
+            &mut self,
+            x: u16,
+            y: u16,
+            width: u16,
+            height: u16,
+        ) -> Result<(), Self::Error> {
+            block_on(self.display.set_ram_window(x, y, width, height))
+        }
+
+        fn reset_ram_window(&mut self) -> Result<(), Self::Error> {
+            block_on(self.display.reset_ram_window())
+        }
+
+        fn set_ram_counter_x(&mut self, x: u16) -> Result<(), Self::Error> {
+            block_on(self.display.set_ram_counter_x(x))
+        }
+
+        fn set_ram_counter_y(&mut self, y: u16) -> Result<(), Self::Error> {
+            block_on(self.display.set_ram_counter_y(y))
+        }
+
+        fn write_bw_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+            block_on(self.display.write_bw_bytes(data))
+        }
+
+        fn write_red_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+            block_on(self.display.write_red_bytes(data))
+        }
+
+        fn refresh_screen_custom_sequence(&mut self, sequence: u8) -> Result<(), Self::Error> {
+            block_on(self.display.refresh_screen_custom_sequence(sequence))
+        }
+
+        fn full_refresh(&mut self) -> Result<(), Self::Error> {
+            block_on(self.display.full_refresh())
+        }
+
+        fn partial_refresh(&mut self) -> Result<(), Self::Error> {
+            block_on(self.display.partial_refresh())
+        }
+
+        fn enter_deep_sleep(&mut self) -> Result<(), Self::Error> {
+            block_on(self.display.enter_deep_sleep())
+        }
+    }
+}
@@ -1,32 +1,173 @@
+use super::{
+    band::Band,
+    commands::SsdCommand,
+    config::{
+        AutoWritePattern, DisplayConfig, DisplayOption, PartialRefreshMode, RamPlane,
+        UpdateRamOption, VDBMode,
+    },
+    dirty::DirtyRegions,
+    error::Error,
+    frame_check::{Checksum, RamPosition, RamTracker, UpdateStatus},
+    framebuffer::FrameBuffer,
+    interface::{Interface, SpiInterface},
+    refresh_policy::{Clock, RefreshKind, RefreshManager},
+};
 use embedded_hal::{
     delay::DelayNs,
     digital::{InputPin, OutputPin},
     spi::SpiDevice,
 };
-
-use super::{
-    commands::SsdCommand,
-    config::{DisplayConfig, UpdateRamOption, VDBMode},
-    error::Error,
+#[cfg(feature = "async")]
+use embedded_hal_async::{
+    delay::DelayNs as AsyncDelayNs, digital::Wait, spi::SpiDevice as AsyncSpiDevice,
 };
 
+#[cfg(feature = "async")]
+use crate::interface::AsyncInterface;
+
+/// Size of the stack buffer used to stream data built on the fly (iterators, repeated bytes).
+const STREAM_CHUNK_SIZE: usize = 64;
+
+/// Bit of the display update control 2 sequence selecting Display Mode 2.
+const DISPLAY_MODE_2: u8 = 0x08;
+
+/// A region without columns or without rows.
+fn is_empty_region(width: u16, height: u16) -> bool {
+    width == 0 || height == 0
+}
+
+/// BUSY pin of the async driver.
+///
+/// Pins implementing `Wait` are waited on with an edge interrupt, wrap the pin in a [`PollingBusy`] to poll it
+/// instead (HALs without `Wait`, or pins without interrupt).
+#[cfg(feature = "async")]
+#[allow(async_fn_in_trait)]
+pub trait AsyncBusyPin: InputPin {
+    /// Wait until the pin is low.
+    async fn wait_until_idle<DELAY: AsyncDelayNs>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "async")]
+impl<T: InputPin + Wait> AsyncBusyPin for T {
+    async fn wait_until_idle<DELAY: AsyncDelayNs>(
+        &mut self,
+        _delay: &mut DELAY,
+    ) -> Result<(), Self::Error> {
+        self.wait_for_low().await
+    }
+}
+
+/// BUSY pin polled with `InputPin::is_high`, sleeping with the async delay between two reads.
+#[cfg(feature = "async")]
+pub struct PollingBusy<P> {
+    pin: P,
+    interval_us: u32,
+}
+
+#[cfg(feature = "async")]
+impl<P: InputPin> PollingBusy<P> {
+    /// Poll the pin every millisecond.
+    pub fn new(pin: P) -> Self {
+        PollingBusy {
+            pin,
+            interval_us: 1000,
+        }
+    }
+
+    pub fn with_interval_us(mut self, interval_us: u32) -> Self {
+        self.interval_us = interval_us;
+        self
+    }
+
+    pub fn into_inner(self) -> P {
+        self.pin
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P: InputPin> embedded_hal::digital::ErrorType for PollingBusy<P> {
+    type Error = P::Error;
+}
+
+#[cfg(feature = "async")]
+impl<P: InputPin> InputPin for PollingBusy<P> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_low()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P: InputPin> AsyncBusyPin for PollingBusy<P> {
+    async fn wait_until_idle<DELAY: AsyncDelayNs>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Self::Error> {
+        while self.pin.is_high()? {
+            delay.delay_us(self.interval_us).await;
+        }
+        Ok(())
+    }
+}
+
 /// Struct representing the connexion with the SSD1680 display driver.
-pub struct SSD1680<RST: OutputPin, DC: OutputPin, BUSY: InputPin, DELAY: DelayNs, SPI: SpiDevice> {
+///
+/// Commands and data go through the interface `DI`: [`SpiInterface`] (SPI device and DC pin, built by `new`),
+/// [`SpiBusInterface`](crate::interface::SpiBusInterface) (SPI bus, CS and DC pins) or any other implementation of
+/// the interface trait, given to `with_interface`.
+///
+/// The async driver (`driver_async::SSD1680`) is generated from this code by `maybe_async_cfg`: the code below is
+/// written for the async version, the blocking version drops the `.await`s and uses the blocking traits.
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "SSD1680Async"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs"),
+        AsyncSpiDevice(sync = "SpiDevice", async = "AsyncSpiDevice")
+    )
+)]
+pub struct SSD1680<RST: OutputPin, BUSY: AsyncBusyPin, DELAY: AsyncDelayNs, DI: AsyncInterface> {
     rst_pin: RST,
-    dc: DC,
     busy: BUSY,
-    spi: SPI,
+    interface: DI,
     delay: DELAY,
     config: DisplayConfig,
+    ram: RamTracker,
+    /// True when the BUSY pin is known to be low, no command started an operation since it was checked
+    busy_idle: bool,
+    /// Sequence of the refresh started by `start_refresh`, while BUSY was not seen low since
+    refresh_sequence: Option<u8>,
+    /// A wait on BUSY did not complete (async future dropped)
+    needs_reinit: bool,
+    /// The panel shows an unknown image, after an aborted refresh
+    content_undefined: bool,
+    /// Regions changed by the last Display Mode 2 update of a frame buffer, the black/white RAM still holds the
+    /// frame before them after the ping-pong swap
+    ping_pong_pending: DirtyRegions,
 }
 
-impl<RST: OutputPin, DC: OutputPin, BUSY: InputPin, DELAY: DelayNs, SPI: SpiDevice, S, R, D, B>
-    SSD1680<RST, DC, BUSY, DELAY, SPI>
-where
-    SPI: SpiDevice<Error = S>,
-    RST: OutputPin<Error = R>,
-    DC: OutputPin<Error = D>,
-    BUSY: InputPin<Error = B>,
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "SSD1680Async"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs"),
+        AsyncSpiDevice(sync = "SpiDevice", async = "AsyncSpiDevice")
+    )
+)]
+impl<RST: OutputPin, DC: OutputPin, BUSY: AsyncBusyPin, DELAY: AsyncDelayNs, SPI: AsyncSpiDevice>
+    SSD1680<RST, BUSY, DELAY, SpiInterface<SPI, DC>>
 {
     pub fn new(
         rst_pin: RST,
@@ -35,127 +176,309 @@ where
         delay: DELAY,
         spi: SPI,
         config: DisplayConfig,
+    ) -> Self {
+        Self::with_interface(rst_pin, busy, delay, SpiInterface::new(spi, dc), config)
+    }
+
+    /// Use a separate SPI device for reads, usually the same bus configured with a lower clock speed.
+    pub fn with_read_spi<RS: AsyncSpiDevice<Error = SPI::Error>>(
+        self,
+        read_spi: RS,
+    ) -> SSD1680<RST, BUSY, DELAY, SpiInterface<SPI, DC, RS>> {
+        SSD1680 {
+            rst_pin: self.rst_pin,
+            busy: self.busy,
+            interface: self.interface.with_read_spi(read_spi),
+            delay: self.delay,
+            config: self.config,
+            ram: self.ram,
+            busy_idle: self.busy_idle,
+            refresh_sequence: self.refresh_sequence,
+            needs_reinit: self.needs_reinit,
+            content_undefined: self.content_undefined,
+            ping_pong_pending: self.ping_pong_pending,
+        }
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "SSD1680Async"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs"),
+        AsyncSpiDevice(sync = "SpiDevice", async = "AsyncSpiDevice")
+    )
+)]
+impl<RST: OutputPin, BUSY: AsyncBusyPin, DELAY: AsyncDelayNs, DI: AsyncInterface>
+    SSD1680<RST, BUSY, DELAY, DI>
+{
+    /// Create the driver on any interface, for example a [`SpiBusInterface`](crate::interface::SpiBusInterface).
+    pub fn with_interface(
+        rst_pin: RST,
+        busy: BUSY,
+        delay: DELAY,
+        interface: DI,
+        config: DisplayConfig,
     ) -> Self {
         SSD1680 {
             rst_pin,
-            dc,
             busy,
-            spi,
+            interface,
             delay,
             config,
+            ram: RamTracker::new(),
+            busy_idle: false,
+            refresh_sequence: None,
+            needs_reinit: false,
+            content_undefined: false,
+            ping_pong_pending: DirtyRegions::new(),
         }
     }
 
+    pub fn interface(&self) -> &DI {
+        &self.interface
+    }
+
+    /// Access the interface directly. Commands sent this way are not seen by the driver, which may skip writes it
+    /// believes redundant: call `hw_init` afterwards if the RAM or the configuration was changed.
+    pub fn interface_mut(&mut self) -> &mut DI {
+        &mut self.interface
+    }
+
+    /// Give back the pins, the delay and the interface.
+    pub fn release_parts(self) -> (RST, BUSY, DELAY, DI) {
+        (self.rst_pin, self.busy, self.delay, self.interface)
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    sync(keep_self),
+    async(feature = "async", self = "SSD1680Async"),
+    idents(
+        SSD1680(sync = "SSD1680", async = "SSD1680Async"),
+        AsyncInterface(sync = "Interface", async = "AsyncInterface"),
+        AsyncBusyPin(sync = "InputPin", async = "AsyncBusyPin"),
+        AsyncDelayNs(sync = "DelayNs", async = "AsyncDelayNs"),
+        AsyncSpiDevice(sync = "SpiDevice", async = "AsyncSpiDevice")
+    )
+)]
+impl<RST, BUSY, DELAY, DI, S, R, D, B> SSD1680<RST, BUSY, DELAY, DI>
+where
+    RST: OutputPin<Error = R>,
+    BUSY: AsyncBusyPin<Error = B>,
+    DELAY: AsyncDelayNs,
+    DI: AsyncInterface<BusError = S, PinError = D>,
+{
+    /// Configuration of the display.
+    pub fn config(&self) -> &DisplayConfig {
+        &self.config
+    }
+
     /// Write a byte to the black/white RAM.
-    pub fn write_bw_byte(&mut self, byte: u8) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::WriteRamBW)?;
-        self.write_data(&[byte])?;
-        Ok(())
+    pub async fn write_bw_byte(&mut self, byte: u8) -> Result<(), Error<S, R, D, B>> {
+        self.write_bw_bytes(&[byte]).await
     }
 
     /// Write bytes to the black/white RAM.
-    pub fn write_bw_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::WriteRamBW)?;
-        self.write_data(bytes)?;
+    pub async fn write_bw_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<S, R, D, B>> {
+        let position = self.ram.position();
+        self.write_command(SsdCommand::WriteRamBW).await?;
+        self.write_data(bytes).await?;
+        self.ram
+            .record_write(RamPlane::BlackWhite, position, Checksum::of(bytes));
         Ok(())
     }
 
+    /// Same as `write_bw_bytes`, but nothing is sent if the same bytes were the last written at the same RAM window and counters.
+    pub async fn write_bw_bytes_if_changed(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        if let Some(position) = self.ram.position()
+            && self
+                .ram
+                .is_written(RamPlane::BlackWhite, position, Checksum::of(bytes))
+        {
+            return Ok(UpdateStatus::Unchanged);
+        }
+        self.write_bw_bytes(bytes).await?;
+        Ok(UpdateStatus::Updated)
+    }
+
     /// Write a byte to the red RAM.
-    pub fn write_red_byte(&mut self, byte: u8) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::WriteRamRed)?;
-        self.write_data(&[byte])?;
-        Ok(())
+    pub async fn write_red_byte(&mut self, byte: u8) -> Result<(), Error<S, R, D, B>> {
+        self.write_red_bytes(&[byte]).await
     }
 
     /// Write bytes to the red RAM.
-    pub fn write_red_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::WriteRamRed)?;
-        self.write_data(bytes)?;
+    pub async fn write_red_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<S, R, D, B>> {
+        let position = self.ram.position();
+        self.write_command(SsdCommand::WriteRamRed).await?;
+        self.write_data(bytes).await?;
+        self.ram
+            .record_write(RamPlane::Red, position, Checksum::of(bytes));
         Ok(())
     }
 
+    /// Same as `write_red_bytes`, but nothing is sent if the same bytes were the last written at the same RAM window and counters.
+    pub async fn write_red_bytes_if_changed(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        if let Some(position) = self.ram.position()
+            && self
+                .ram
+                .is_written(RamPlane::Red, position, Checksum::of(bytes))
+        {
+            return Ok(UpdateStatus::Unchanged);
+        }
+        self.write_red_bytes(bytes).await?;
+        Ok(UpdateStatus::Updated)
+    }
+
     /// Initialize the hardware according to the configuration.
     /// This function is to be used to initialize the hardware the first time, and to reinitialize it after putting it into deep sleep.
-    pub fn hw_init(&mut self) -> Result<(), Error<S, R, D, B>> {
+    pub async fn hw_init(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.hw_reset().await?;
+        self.hw_init_after_reset().await
+    }
+
+    /// Pulse the RST pin, the first half of `hw_init`.
+    pub async fn hw_reset(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.assume_reset()?;
         self.rst_pin.set_low().map_err(Error::RstPinError)?;
-        self.delay.delay_ms(20);
+        self.delay.delay_ms(20).await;
         self.rst_pin.set_high().map_err(Error::RstPinError)?;
-        self.delay.delay_ms(20);
+        self.delay.delay_ms(20).await;
+        Ok(())
+    }
+
+    /// Same as `hw_reset` with another pin, a reset line shared with other displays.
+    pub async fn hw_reset_with<P: OutputPin<Error = R>>(
+        &mut self,
+        rst: &mut P,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.assume_reset()?;
+        rst.set_low().map_err(Error::RstPinError)?;
+        self.delay.delay_ms(20).await;
+        rst.set_high().map_err(Error::RstPinError)?;
+        self.delay.delay_ms(20).await;
+        Ok(())
+    }
+
+    /// Record a reset done without the driver, by `hw_reset_with` on another display sharing the reset line.
+    /// Follow with `hw_init_after_reset`.
+    pub fn assume_reset(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.check_no_refresh()?;
+        self.needs_reinit = false;
+        self.busy_idle = false;
+        self.ping_pong_pending.clear();
+        Ok(())
+    }
 
-        self.wait_for_busy()?;
-        self.sw_reset()?;
-        self.wait_for_busy()?;
+    /// Initialize the controller after `hw_reset`, the second half of `hw_init`.
+    ///
+    /// Lets displays sharing a reset line all be reset before any of them is initialized.
+    pub async fn hw_init_after_reset(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.wait_for_busy().await?;
+        self.sw_reset().await?;
+        self.wait_for_busy().await?;
 
         self.output_control(
             self.config.height,
             self.config.gate_scanning_gd,
             self.config.gate_scanning_sm,
             self.config.gate_scanning_tb,
-        )?;
+        )
+        .await?;
 
-        self.data_entry_mode(true, true, false)?;
+        self.data_entry_mode(true, true, false).await?;
 
-        self.set_ram_start_end_x(0, self.config.width / 8 - 1)?;
-        self.set_ram_start_end_y(0, self.config.height - 1)?;
+        self.set_ram_start_end_x(0, self.config.width.div_ceil(8) - 1)
+            .await?;
+        self.set_ram_start_end_y(0, self.config.height - 1).await?;
 
-        self.set_border_waveform(self.config.border_waveform_control)?;
+        self.set_border_waveform(self.config.border_waveform_control)
+            .await?;
 
-        self.display_update_control_1(
-            self.config.ram_content_for_display_update,
-            self.config.ram_content_for_display_update,
-            self.config.s8_source_output_mode,
-        )?;
+        self.apply_ram_content_options().await?;
+
+        self.select_internal_temperature_sensor(self.config.use_internal_temperature_sensor)
+            .await?;
+
+        self.set_ram_counter_x(0).await?;
+        self.set_ram_counter_y(0).await?;
+        self.wait_for_busy().await?;
 
-        self.select_internal_temperature_sensor(self.config.use_internal_temperature_sensor)?;
+        let ping_pong = self.config.partial_refresh_mode == PartialRefreshMode::PingPong;
+        if let Some(option) = self.config.display_option {
+            self.set_display_option(option.with_ping_pong(option.ping_pong || ping_pong))
+                .await?;
+        } else if ping_pong {
+            self.set_display_option(DisplayOption::default().with_ping_pong(true))
+                .await?;
+        }
 
-        self.set_ram_counter_x(0)?;
-        self.set_ram_counter_y(0)?;
-        self.wait_for_busy()?;
+        if self.config.partial_refresh_mode != PartialRefreshMode::Standalone {
+            // both planes need to agree before the first partial refresh
+            self.fill_bw_screen_internal(true).await?;
+            self.fill_red_screen_internal(true).await?;
+            self.set_ram_counter_x(0).await?;
+            self.set_ram_counter_y(0).await?;
+        }
 
         Ok(())
     }
 
     /// Set the ram counter x position, this represents the data bank (8bits), so you need to divide by 8
-    pub fn set_ram_counter_x(&mut self, x: u16) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::SetRamXCounter)?;
-        self.write_data(&[x as u8])?;
+    pub async fn set_ram_counter_x(&mut self, x: u16) -> Result<(), Error<S, R, D, B>> {
+        self.ram.set_counter_x(x);
+        self.write_command(SsdCommand::SetRamXCounter).await?;
+        self.write_data(&[x as u8]).await?;
         Ok(())
     }
 
     /// Set the ram counter y position.
-    pub fn set_ram_counter_y(&mut self, y: u16) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::SetRamYCounter)?;
-        self.write_data(&[y as u8, (y >> 8) as u8])?;
+    pub async fn set_ram_counter_y(&mut self, y: u16) -> Result<(), Error<S, R, D, B>> {
+        self.ram.set_counter_y(y);
+        self.write_command(SsdCommand::SetRamYCounter).await?;
+        self.write_data(&[y as u8, (y >> 8) as u8]).await?;
         Ok(())
     }
 
     /// Use the internal temperature sensor
-    pub fn select_internal_temperature_sensor(
+    pub async fn select_internal_temperature_sensor(
         &mut self,
         internal: bool,
     ) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::TemperatureSensorControl)?;
-        self.write_data(&[if internal { 0x80 } else { 0x48 }])?;
+        self.write_command(SsdCommand::TemperatureSensorControl)
+            .await?;
+        self.write_data(&[if internal { 0x80 } else { 0x48 }])
+            .await?;
         Ok(())
     }
 
     /// Set the border waveform mode.
-    pub fn set_border_waveform(&mut self, mode: VDBMode) -> Result<(), Error<S, R, D, B>> {
+    pub async fn set_border_waveform(&mut self, mode: VDBMode) -> Result<(), Error<S, R, D, B>> {
         let data = match mode {
             VDBMode::VCOM => 0x80,
             VDBMode::HiZ => 0xC0,
             VDBMode::FixLevel(level) => 0x10 | level.into_u8(),
             VDBMode::GSTransition(follow_lut, lut) => ((follow_lut as u8) << 2) | lut.into_u8(),
         };
-        self.write_command(SsdCommand::BorderWaveformnControl)?;
-        self.write_data(&[data])?;
+        self.write_command(SsdCommand::BorderWaveformnControl)
+            .await?;
+        self.write_data(&[data]).await?;
         Ok(())
     }
 
     /// red : red ram data
     /// bw : black and white ram data
     /// source_output_mode : source output mode true is "Available Source from S8 to S167", false is "Available Source from S0 to S175"
-    pub fn display_update_control_1(
+    pub async fn display_update_control_1(
         &mut self,
         red: UpdateRamOption,
         bw: UpdateRamOption,
@@ -164,30 +487,117 @@ where
         let first_byte: u8 = bw as u8 | ((red as u8) << 4);
         let second_byte = (source_output_mode as u8) << 7;
 
-        self.write_command(SsdCommand::DisplayUpdateControl1)?;
-        self.write_data(&[first_byte, second_byte])?;
+        self.write_command(SsdCommand::DisplayUpdateControl1)
+            .await?;
+        self.write_data(&[first_byte, second_byte]).await?;
         Ok(())
     }
 
-    pub fn set_ram_start_end_x(&mut self, start: u16, end: u16) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::SetRamXStartEnd)?;
-        self.write_data(&[start as u8, end as u8])?;
+    /// Change how the black/white RAM is shown (normal, inverted or bypassed), without sending the image again.
+    ///
+    /// The configuration is updated and the change is visible at the next refresh. Partial refreshes compare the
+    /// two planes, prefer a full refresh after a change.
+    pub async fn set_bw_ram_option(
+        &mut self,
+        option: UpdateRamOption,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.config.bw_ram_content_for_display_update = option;
+        self.apply_ram_content_options().await
+    }
+
+    /// Change how the red RAM is shown, for example `Bypass0` to hide the red layer.
+    ///
+    /// The configuration is updated and the change is visible at the next refresh.
+    pub async fn set_red_ram_option(
+        &mut self,
+        option: UpdateRamOption,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.config.red_ram_content_for_display_update = option;
+        self.apply_ram_content_options().await
+    }
+
+    /// Send the RAM options of the configuration with display update control 1.
+    async fn apply_ram_content_options(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.display_update_control_1(
+            self.config.red_ram_content_for_display_update,
+            self.config.bw_ram_content_for_display_update,
+            self.config.s8_source_output_mode,
+        )
+        .await
+    }
+
+    pub async fn set_ram_start_end_x(
+        &mut self,
+        start: u16,
+        end: u16,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.ram.set_window_x(start, end);
+        self.write_command(SsdCommand::SetRamXStartEnd).await?;
+        self.write_data(&[start as u8, end as u8]).await?;
+        Ok(())
+    }
+    pub async fn set_ram_start_end_y(
+        &mut self,
+        start: u16,
+        end: u16,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.ram.set_window_y(start, end);
+        self.write_command(SsdCommand::SetRamYStartEnd).await?;
+        self.write_data(&[start as u8, (start >> 8) as u8, end as u8, (end >> 8) as u8])
+            .await?;
         Ok(())
     }
-    pub fn set_ram_start_end_y(&mut self, start: u16, end: u16) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::SetRamYStartEnd)?;
-        self.write_data(&[start as u8, (start >> 8) as u8, end as u8, (end >> 8) as u8])?;
+
+    /// Write the display option register (ping-pong, Display Mode 2 waveform settings, ...)
+    pub async fn set_display_option(
+        &mut self,
+        option: DisplayOption,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::WriteRegisterDisplayOption)
+            .await?;
+        self.write_data(&option.into_bytes()).await?;
         Ok(())
     }
 
+    /// Restrict RAM writes to a window and move the RAM counters to its start.
+    ///
+    /// `x` and `width` are in pixels, `x` must be a multiple of 8 and the window covers `width.div_ceil(8)` bytes per
+    /// row, like the rows of a [`FrameBuffer`]. An empty region (`width` or `height` of 0) has no window, nothing is sent.
+    pub async fn set_ram_window(
+        &mut self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+    ) -> Result<(), Error<S, R, D, B>> {
+        if is_empty_region(width, height) {
+            return Ok(());
+        }
+        self.set_ram_start_end_x(x / 8, (x + width).div_ceil(8) - 1)
+            .await?;
+        self.set_ram_start_end_y(y, y + height - 1).await?;
+        self.set_ram_counter_x(x / 8).await?;
+        self.set_ram_counter_y(y).await?;
+        Ok(())
+    }
+
+    /// Set the RAM window back to the whole screen, as done by `hw_init`.
+    pub async fn reset_ram_window(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.set_ram_window(0, 0, self.config.width, self.config.height)
+            .await
+    }
+
     /// Write LUT Register, 153 bytes long
-    pub fn write_lut_register(&mut self, register: &[u8; 153]) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::WriteLutRegister)?;
-        self.write_data(register)?;
+    pub async fn write_lut_register(
+        &mut self,
+        register: &[u8; 153],
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::WriteLutRegister).await?;
+        self.write_data(register).await?;
         Ok(())
     }
 
-    pub fn output_control(
+    pub async fn output_control(
         &mut self,
         height: u16,
         gd: bool,
@@ -195,139 +605,1074 @@ where
         tb: bool,
     ) -> Result<(), Error<S, R, D, B>> {
         let height = if height == 0 { 0 } else { height - 1 };
-        self.write_command(SsdCommand::DriveOutputControl)?;
+        self.write_command(SsdCommand::DriveOutputControl).await?;
         let gate_scanning: u8 = tb as u8 | ((sm as u8) << 1) | ((gd as u8) << 2);
-        self.write_data(&[height as u8, (height >> 8) as u8, gate_scanning])?;
+        self.write_data(&[height as u8, (height >> 8) as u8, gate_scanning])
+            .await?;
         Ok(())
     }
 
     /// For x and y, true means increment/false means decrement
     /// For direction, true means the address counter is updated in the Y direction after data has been written to the RAM, false will update the X counter.
-    pub fn data_entry_mode(
+    pub async fn data_entry_mode(
         &mut self,
         x: bool,
         y: bool,
         direction: bool,
     ) -> Result<(), Error<S, R, D, B>> {
         let sequence: u8 = (x as u8) | ((y as u8) << 1) | ((direction as u8) << 2);
-        self.write_command(SsdCommand::DataEntryModeSetting)?;
-        self.write_data(&[sequence])?;
+        self.write_command(SsdCommand::DataEntryModeSetting).await?;
+        self.write_data(&[sequence]).await?;
 
         Ok(())
     }
 
     /// Send the command to soft reset the chip (used by hw_init)
-    pub fn sw_reset(&mut self) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::SWReset)
+    pub async fn sw_reset(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::SWReset).await?;
+        self.interface.release().await?;
+        Ok(())
     }
 
     /// Wait until the busy pin is low, meaning the chip is ready to receive new commands or data.
+    /// Returns immediately if no command started a busy operation since the last wait.
+    #[maybe_async_cfg::only_if(sync)]
     pub fn wait_for_busy(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.interface.release()?;
+        if self.busy_idle {
+            return Ok(());
+        }
         while self.busy.is_high().map_err(Error::BusyPinError)? {
             self.delay.delay_ms(1);
         }
+        self.busy_idle = true;
+        self.refresh_done();
+        Ok(())
+    }
+
+    /// Wait until the busy pin is low, meaning the chip is ready to receive new commands or data.
+    /// Returns immediately if no command started a busy operation since the last wait.
+    #[maybe_async_cfg::only_if(async)]
+    pub async fn wait_for_busy(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.interface.release().await?;
+        if self.busy_idle {
+            return Ok(());
+        }
+        // stays set if the future is dropped during the wait
+        self.needs_reinit = true;
+        let result = self.busy.wait_until_idle(&mut self.delay).await;
+        self.needs_reinit = false;
+        result.map_err(Error::BusyPinError)?;
+        self.busy_idle = true;
+        self.refresh_done();
         Ok(())
     }
 
+    /// BUSY went low after a refresh started by `start_refresh`.
+    fn refresh_done(&mut self) {
+        if self.refresh_sequence.take() == Some(self.config.full_refresh_sequence) {
+            self.content_undefined = false;
+        }
+    }
+
+    /// `max_transfer_size` of the configuration, a public field that may have been set to 0.
+    fn max_transfer_size(&self) -> usize {
+        self.config.max_transfer_size.max(1)
+    }
+
+    /// Fail if a refresh started by `start_refresh` is still running.
+    fn check_no_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
+        if self.refresh_sequence.is_some() {
+            if self.busy.is_high().map_err(Error::BusyPinError)? {
+                return Err(Error::RefreshInProgress);
+            }
+            self.busy_idle = true;
+            self.refresh_done();
+        }
+        Ok(())
+    }
+
+    /// Fail if the controller can not receive a command: refresh running, or interrupted wait.
+    fn check_ready(&mut self) -> Result<(), Error<S, R, D, B>> {
+        if self.needs_reinit {
+            return Err(Error::NeedsReinit);
+        }
+        self.check_no_refresh()
+    }
+
     /// Send a command to the chip.
-    pub fn write_command(&mut self, command: SsdCommand) -> Result<(), Error<S, R, D, B>> {
-        self.wait_for_busy()?;
-        self.dc.set_low().map_err(Error::DcPinError)?;
-        self.spi.write(&[command.into()]).map_err(Error::SpiError)?;
-        // self.wait_for_busy()
+    pub async fn write_command(&mut self, command: SsdCommand) -> Result<(), Error<S, R, D, B>> {
+        self.check_ready()?;
+        self.wait_for_busy().await?;
+        let ping_pong = self.config.partial_refresh_mode == PartialRefreshMode::PingPong;
+        self.ram.on_command(command, ping_pong);
+        self.interface.send_command(command.into()).await?;
+        if command.sets_busy() {
+            self.busy_idle = false;
+        }
+        Ok(())
+    }
+
+    /// Send data to the chip, the parameters or the RAM data of the last command.
+    ///
+    /// The interface is released afterwards (CS deasserted by a `SpiBusInterface`), further data still goes to the same
+    /// command.
+    pub async fn write_data(&mut self, data: &[u8]) -> Result<(), Error<S, R, D, B>> {
+        self.send_data(data).await?;
+        self.interface.release().await?;
+        Ok(())
+    }
+
+    /// Send data in transfers of at most `max_transfer_size` bytes, without releasing the interface.
+    async fn send_data(&mut self, data: &[u8]) -> Result<(), Error<S, R, D, B>> {
+        self.check_ready()?;
+        for chunk in data.chunks(self.max_transfer_size()) {
+            self.interface.send_data(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// Send data to the chip from an iterator, through a small buffer.
+    pub async fn write_data_iter<I: IntoIterator<Item = u8>>(
+        &mut self,
+        data: I,
+    ) -> Result<(), Error<S, R, D, B>> {
+        let mut buffer = [0u8; STREAM_CHUNK_SIZE];
+        let chunk_size = STREAM_CHUNK_SIZE.min(self.max_transfer_size());
+        let mut len = 0;
+        for byte in data {
+            buffer[len] = byte;
+            len += 1;
+            if len == chunk_size {
+                self.send_data(&buffer[..len]).await?;
+                len = 0;
+            }
+        }
+        self.write_data(&buffer[..len]).await
+    }
+
+    /// Send the same data byte `count` times.
+    pub async fn write_data_repeat(
+        &mut self,
+        byte: u8,
+        count: usize,
+    ) -> Result<(), Error<S, R, D, B>> {
+        let buffer = [byte; STREAM_CHUNK_SIZE];
+        let chunk_size = STREAM_CHUNK_SIZE.min(self.max_transfer_size());
+        let mut remaining = count;
+        while remaining > 0 {
+            let len = remaining.min(chunk_size);
+            self.send_data(&buffer[..len]).await?;
+            remaining -= len;
+        }
+        self.interface.release().await?;
         Ok(())
     }
 
-    /// Send data to the chip
-    pub fn write_data(&mut self, data: &[u8]) -> Result<(), Error<S, R, D, B>> {
-        self.dc.set_high().map_err(Error::DcPinError)?;
-        self.spi.write(data).map_err(Error::SpiError)?;
-        // self.wait_for_busy()
+    /// Write bytes from an iterator to one of the RAM planes, from the current RAM counters.
+    pub async fn write_ram_iter<I: IntoIterator<Item = u8>>(
+        &mut self,
+        plane: RamPlane,
+        data: I,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(plane.write_command()).await?;
+        self.write_data_iter(data).await
+    }
+
+    /// Write the same byte `count` times to one of the RAM planes, from the current RAM counters.
+    pub async fn write_ram_repeat(
+        &mut self,
+        plane: RamPlane,
+        byte: u8,
+        count: usize,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(plane.write_command()).await?;
+        self.write_data_repeat(byte, count).await
+    }
+
+    /// Read data from the chip, after a command
+    pub async fn read_data(&mut self, buf: &mut [u8]) -> Result<(), Error<S, R, D, B>> {
+        self.check_ready()?;
+        self.interface.read_data(false, buf).await?;
+        self.interface.release().await?;
         Ok(())
     }
 
     /// Set how the display should be updated
-    pub fn display_update_control_2(
+    pub async fn display_update_control_2(
         &mut self,
         /*TODO: make an enum */ sequence: u8,
     ) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::DisplayUpdateControl2)?;
-        self.write_data(&[sequence])?;
+        self.write_command(SsdCommand::DisplayUpdateControl2)
+            .await?;
+        self.write_data(&[sequence]).await?;
         Ok(())
     }
 
     /// Run the update sequence, the chip will send the correct frequencies to the display to reflect the image in memory
-    pub fn activate_update(&mut self) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::MasterActivation)?;
+    pub async fn activate_update(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::MasterActivation).await?;
+        self.interface.release().await?;
         Ok(())
     }
 
     /// Refresh screen using a custom sequence
     ///
     /// On the 290_T94 screen, 0xF7 is the full refresh sequence and 0xFC is the partial refresh sequence.
-    pub fn refresh_screen_custom_sequence(
+    pub async fn refresh_screen_custom_sequence(
         &mut self,
         sequence: u8,
     ) -> Result<(), Error<S, R, D, B>> {
-        self.display_update_control_2(sequence)?;
+        self.display_update_control_2(sequence).await?;
         // self.delay.delay_ms(20);
-        self.activate_update()?;
-        self.wait_for_busy()
+        self.activate_update().await?;
+        self.wait_for_busy().await?;
+        if sequence == self.config.full_refresh_sequence {
+            self.content_undefined = false;
+        }
+        Ok(())
+    }
+
+    /// Start a refresh with a custom sequence and return without waiting for its end.
+    ///
+    /// Until BUSY goes low, commands fail with `Error::RefreshInProgress`: check the progress with `poll` or
+    /// `is_busy`, or wait for the end with `finish_refresh`.
+    pub async fn start_refresh(&mut self, sequence: u8) -> Result<(), Error<S, R, D, B>> {
+        self.display_update_control_2(sequence).await?;
+        self.activate_update().await?;
+        self.refresh_sequence = Some(sequence);
+        Ok(())
+    }
+
+    /// Level of the BUSY pin, true while the controller is working (refresh, reset, ...).
+    pub fn is_busy(&mut self) -> Result<bool, Error<S, R, D, B>> {
+        self.busy.is_high().map_err(Error::BusyPinError)
+    }
+
+    /// True once the refresh started by `start_refresh` is over (or if none was started).
+    pub fn poll(&mut self) -> Result<bool, Error<S, R, D, B>> {
+        match self.check_no_refresh() {
+            Err(Error::RefreshInProgress) => Ok(false),
+            result => result.map(|_| true),
+        }
+    }
+
+    /// Wait for the end of the refresh started by `start_refresh`.
+    pub async fn finish_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.wait_for_busy().await
+    }
+
+    /// Stop a running refresh: pulse RST and initialize the controller again from the stored configuration.
+    ///
+    /// Also recovers from `Error::NeedsReinit`. The RAM is lost and the image on the panel is undefined
+    /// (see `panel_content_undefined`) until the next full refresh, force it on a `RefreshManager` with `force_full`.
+    pub async fn abort_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.refresh_sequence = None;
+        self.needs_reinit = false;
+        self.content_undefined = true;
+        self.hw_init().await
+    }
+
+    /// True after `abort_refresh`, until a full refresh completes.
+    pub fn panel_content_undefined(&self) -> bool {
+        self.content_undefined
     }
 
     /// Partial refresh using the configured sequence
-    pub fn partial_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
+    pub async fn partial_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
         self.refresh_screen_custom_sequence(self.config.partial_refresh_sequence)
+            .await
+    }
+
+    /// Partial refresh using the configured sequence with Display Mode 2 forced,
+    /// with RAM ping-pong enabled the controller keeps the displayed frame as reference for the next one.
+    pub async fn partial_refresh_mode_2(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.refresh_screen_custom_sequence(self.partial_mode_2_sequence())
+            .await
     }
 
     /// Full refresh using the configured sequence
-    pub fn full_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
+    pub async fn full_refresh(&mut self) -> Result<(), Error<S, R, D, B>> {
         self.refresh_screen_custom_sequence(self.config.full_refresh_sequence)
+            .await
+    }
+
+    /// Refresh the screen, unless the RAM did not change since the last refresh.
+    pub async fn refresh_if_changed(
+        &mut self,
+        kind: RefreshKind,
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        if !self.ram.has_changed_since_refresh() {
+            return Ok(UpdateStatus::Unchanged);
+        }
+        match kind {
+            RefreshKind::Partial => self.partial_refresh().await?,
+            RefreshKind::Full => self.full_refresh().await?,
+        }
+        Ok(UpdateStatus::Updated)
+    }
+
+    /// Refresh the screen, letting the manager choose between a partial and a full refresh.
+    ///
+    /// `changed_area` is the number of pixels that changed since the last refresh.
+    pub async fn refresh<C: Clock>(
+        &mut self,
+        manager: &mut RefreshManager<C>,
+        changed_area: u32,
+    ) -> Result<RefreshKind, Error<S, R, D, B>> {
+        let total_area = self.config.width as u32 * self.config.height as u32;
+        let kind = manager.next_refresh(changed_area, total_area);
+        match kind {
+            RefreshKind::Partial => self.partial_refresh().await?,
+            RefreshKind::Full => self.full_refresh().await?,
+        }
+        manager.record(kind);
+        Ok(kind)
+    }
+
+    /// Write a whole frame to the black/white RAM and display it.
+    ///
+    /// With [`PartialRefreshMode::PreviousFrameInRed`], the frame is then copied to the red RAM to be the reference of the next partial refresh.
+    /// Unless `force` is true, nothing is done if this frame is already displayed.
+    pub async fn show_frame(
+        &mut self,
+        frame: &[u8],
+        kind: RefreshKind,
+        force: bool,
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        let (width, height) = (self.config.width, self.config.height);
+        self.show_region(0, 0, width, height, frame, kind, force)
+            .await
+    }
+
+    /// Write a region of the screen to the black/white RAM and display it.
+    ///
+    /// `x` and `width` are in pixels, `x` must be a multiple of 8, `data` holds `width.div_ceil(8) * height` bytes.
+    /// With [`PartialRefreshMode::PreviousFrameInRed`], the region is then copied to the red RAM to be the reference of the next partial refresh.
+    /// With [`PartialRefreshMode::PingPong`], partial refreshes use Display Mode 2 and swap the planes: the region is then written
+    /// again to the black/white RAM, which holds the frame before the update, and the red RAM is only written after a full refresh.
+    /// The regions of a partial `show_framebuffer` are not known here, follow it with `show_framebuffer` or a whole frame.
+    /// Unless `force` is true, nothing is done if this region is already displayed with the same content.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn show_region(
+        &mut self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+        kind: RefreshKind,
+        force: bool,
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        if is_empty_region(width, height) {
+            return Ok(UpdateStatus::Unchanged);
+        }
+        let position = RamPosition::region(x, y, width, height);
+        if !force
+            && self
+                .ram
+                .is_displayed(RamPlane::BlackWhite, position, Checksum::of(data))
+        {
+            return Ok(UpdateStatus::Unchanged);
+        }
+
+        self.set_ram_window(x, y, width, height).await?;
+        self.write_bw_bytes(data).await?;
+
+        if self.refresh_new_frame(kind).await? {
+            self.set_ram_counter_x(x / 8).await?;
+            self.set_ram_counter_y(y).await?;
+            self.write_red_bytes(data).await?;
+            self.ram.mark_displayed();
+        } else if self.swaps_planes(kind) {
+            self.set_ram_counter_x(x / 8).await?;
+            self.set_ram_counter_y(y).await?;
+            self.write_bw_bytes(data).await?;
+            self.ram.mark_displayed();
+            self.ping_pong_pending.clear();
+        }
+
+        self.reset_ram_window().await?;
+        Ok(UpdateStatus::Updated)
+    }
+
+    /// Send the dirty regions of the frame buffer to the black/white RAM and display them.
+    ///
+    /// The red RAM is handled as in `show_region`.
+    /// Unless `force` is true, nothing is done if the content of the frame buffer is already displayed.
+    pub async fn show_framebuffer<const N: usize>(
+        &mut self,
+        framebuffer: &mut FrameBuffer<N>,
+        kind: RefreshKind,
+        force: bool,
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        let status = self.start_framebuffer(framebuffer, kind, force).await?;
+        if status == UpdateStatus::Updated {
+            self.finish_framebuffer(framebuffer, kind).await?;
+        }
+        Ok(status)
+    }
+
+    /// Send the dirty regions of the frame buffer to the black/white RAM and start the refresh, without waiting for it.
+    ///
+    /// When `Updated` is returned, complete it with `finish_framebuffer`, with the same frame buffer and kind.
+    pub async fn start_framebuffer<const N: usize>(
+        &mut self,
+        framebuffer: &mut FrameBuffer<N>,
+        kind: RefreshKind,
+        force: bool,
+    ) -> Result<UpdateStatus, Error<S, R, D, B>> {
+        let position = RamPosition::region(0, 0, framebuffer.width(), framebuffer.height());
+        let checksum = Checksum::of(framebuffer.as_bytes());
+        if !force
+            && self
+                .ram
+                .is_displayed(RamPlane::BlackWhite, position, checksum)
+        {
+            framebuffer.clear_dirty();
+            return Ok(UpdateStatus::Unchanged);
+        }
+
+        // after a Display Mode 2 update, the RAM written holds the frame before it: send its regions again
+        let mut regions = framebuffer.dirty().clone();
+        for rect in self.ping_pong_pending.rects() {
+            regions.add(*rect);
+        }
+        self.write_dirty_regions(framebuffer, &regions, SsdCommand::WriteRamBW)
+            .await?;
+        self.ram
+            .record_write(RamPlane::BlackWhite, Some(position), checksum);
+        self.start_refresh(self.new_frame_sequence(kind)).await?;
+        self.ping_pong_pending = if self.swaps_planes(kind) {
+            framebuffer.dirty().clone()
+        } else {
+            DirtyRegions::new()
+        };
+        Ok(UpdateStatus::Updated)
+    }
+
+    /// Wait for the refresh started by `start_framebuffer`, then update the red RAM as in `show_framebuffer`.
+    pub async fn finish_framebuffer<const N: usize>(
+        &mut self,
+        framebuffer: &mut FrameBuffer<N>,
+        kind: RefreshKind,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.finish_refresh().await?;
+        if self.red_reference_needed(kind) {
+            let position = RamPosition::region(0, 0, framebuffer.width(), framebuffer.height());
+            self.write_dirty_regions(framebuffer, framebuffer.dirty(), SsdCommand::WriteRamRed)
+                .await?;
+            self.ram.record_write(
+                RamPlane::Red,
+                Some(position),
+                Checksum::of(framebuffer.as_bytes()),
+            );
+            self.ram.mark_displayed();
+        }
+        self.reset_ram_window().await?;
+
+        framebuffer.clear_dirty();
+        Ok(())
+    }
+
+    /// Send the dirty regions of the frame buffer and display them, letting the manager choose the kind of refresh.
+    /// Returns `None` when the frame buffer content is already displayed and `force` is false.
+    pub async fn refresh_framebuffer<C: Clock, const N: usize>(
+        &mut self,
+        manager: &mut RefreshManager<C>,
+        framebuffer: &mut FrameBuffer<N>,
+        force: bool,
+    ) -> Result<Option<RefreshKind>, Error<S, R, D, B>> {
+        let total_area = self.config.width as u32 * self.config.height as u32;
+        let kind =
+            self.new_frame_kind(manager.next_refresh(framebuffer.dirty().area(), total_area));
+        match self.show_framebuffer(framebuffer, kind, force).await? {
+            UpdateStatus::Updated => {
+                manager.record(kind);
+                Ok(Some(kind))
+            }
+            UpdateStatus::Unchanged => Ok(None),
+        }
+    }
+
+    /// Render the screen strip by strip, for devices without memory for a frame buffer.
+    ///
+    /// `draw` is called once per strip and draws the whole screen, only the pixels of the current strip are kept.
+    /// Each strip is then written to `plane` through a RAM window. `buffer` holds at least one row of `width.div_ceil(8)`
+    /// bytes, `Error::BufferTooSmall` is returned otherwise.
+    pub async fn draw_in_bands<F: FnMut(&mut Band<'_>)>(
+        &mut self,
+        plane: RamPlane,
+        buffer: &mut [u8],
+        mut draw: F,
+    ) -> Result<(), Error<S, R, D, B>> {
+        let (width, height) = (self.config.width, self.config.height);
+        if buffer.len() < width.div_ceil(8) as usize {
+            return Err(Error::BufferTooSmall);
+        }
+        let mut band = Band::new(buffer, width, height, plane);
+
+        let mut y = 0;
+        while y < height {
+            band.start_at(y);
+            draw(&mut band);
+
+            self.set_ram_window(0, y, width, band.rows()).await?;
+            match plane {
+                RamPlane::BlackWhite => self.write_bw_bytes(band.as_bytes()).await?,
+                RamPlane::Red => self.write_red_bytes(band.as_bytes()).await?,
+            }
+            y += band.rows();
+        }
+
+        self.reset_ram_window().await
+    }
+
+    /// Write each of `regions` from the frame buffer through its own RAM window.
+    async fn write_dirty_regions<const N: usize>(
+        &mut self,
+        framebuffer: &FrameBuffer<N>,
+        regions: &DirtyRegions,
+        command: SsdCommand,
+    ) -> Result<(), Error<S, R, D, B>> {
+        let stride = framebuffer.stride();
+        for rect in regions.rects() {
+            self.set_ram_window(rect.x, rect.y, rect.width, rect.height)
+                .await?;
+            self.write_command(command).await?;
+            if rect.x == 0 && rect.width as usize / 8 == stride {
+                // whole rows are contiguous in the frame buffer
+                let start = rect.y as usize * stride;
+                let end = start + rect.height as usize * stride;
+                self.write_data(&framebuffer.as_bytes()[start..end]).await?;
+            } else {
+                for y in rect.y..rect.y + rect.height {
+                    self.send_data(framebuffer.row_bytes(rect, y)).await?;
+                }
+                self.interface.release().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Refresh the new frame written in the black/white RAM according to the partial refresh mode.
+    /// Returns true when the frame also has to be written to the red RAM, as reference for the next partial refresh.
+    async fn refresh_new_frame(&mut self, kind: RefreshKind) -> Result<bool, Error<S, R, D, B>> {
+        self.refresh_screen_custom_sequence(self.new_frame_sequence(kind))
+            .await?;
+        Ok(self.red_reference_needed(kind))
+    }
+
+    /// Kind of refresh actually used to show a new frame.
+    ///
+    /// After `hw_init` the planes are filled with white while the panel still shows the old image, a partial
+    /// refresh against them would ghost: the first new frame is shown with a full refresh.
+    fn new_frame_kind(&self, kind: RefreshKind) -> RefreshKind {
+        if self.config.partial_refresh_mode != PartialRefreshMode::Standalone
+            && self.ram.is_reference_stale()
+        {
+            RefreshKind::Full
+        } else {
+            kind
+        }
+    }
+
+    /// Configured partial refresh sequence, with Display Mode 2 forced.
+    fn partial_mode_2_sequence(&self) -> u8 {
+        self.config.partial_refresh_sequence | DISPLAY_MODE_2
+    }
+
+    /// Update sequence showing a new frame according to the partial refresh mode.
+    fn new_frame_sequence(&self, kind: RefreshKind) -> u8 {
+        match self.new_frame_kind(kind) {
+            RefreshKind::Partial
+                if self.config.partial_refresh_mode == PartialRefreshMode::PingPong =>
+            {
+                self.partial_mode_2_sequence()
+            }
+            RefreshKind::Partial => self.config.partial_refresh_sequence,
+            RefreshKind::Full => self.config.full_refresh_sequence,
+        }
+    }
+
+    /// True when a new frame also has to be written to the red RAM, as reference for the next partial refresh.
+    fn red_reference_needed(&self, kind: RefreshKind) -> bool {
+        match self.config.partial_refresh_mode {
+            PartialRefreshMode::Standalone => false,
+            PartialRefreshMode::PreviousFrameInRed => true,
+            PartialRefreshMode::PingPong => !self.swaps_planes(kind),
+        }
+    }
+
+    /// True when showing a new frame swaps the planes: a Display Mode 2 update with RAM ping-pong.
+    ///
+    /// The red RAM then holds the new frame, and the black/white RAM the frame before it.
+    fn swaps_planes(&self, kind: RefreshKind) -> bool {
+        self.config.partial_refresh_mode == PartialRefreshMode::PingPong
+            && self.new_frame_kind(kind) == RefreshKind::Partial
+    }
+
+    /// Let the ssd1680 fill the RAM window of a plane with a pattern, no pixel data is sent.
+    pub async fn auto_write(
+        &mut self,
+        plane: RamPlane,
+        pattern: AutoWritePattern,
+    ) -> Result<(), Error<S, R, D, B>> {
+        self.wait_for_busy().await?;
+
+        let command = match plane {
+            RamPlane::BlackWhite => SsdCommand::AutoWriteBWRam,
+            RamPlane::Red => SsdCommand::AutoWriteRedRam,
+        };
+        self.write_command(command).await?;
+        self.write_data(&[pattern.into_u8()]).await?;
+        self.wait_for_busy().await?;
+        Ok(())
+    }
+
+    /// Let the ssd1680 fill a rectangle of a plane with a pattern, through the RAM window.
+    ///
+    /// `x` and `width` are in pixels, `x` must be a multiple of 8 and `width` is rounded up to whole bytes.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fill_rect_internal(
+        &mut self,
+        plane: RamPlane,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        pattern: AutoWritePattern,
+    ) -> Result<(), Error<S, R, D, B>> {
+        if is_empty_region(width, height) {
+            return Ok(());
+        }
+        self.set_ram_window(x, y, width, height).await?;
+        self.auto_write(plane, pattern).await?;
+        self.reset_ram_window().await
     }
 
     /// Let the ssd1680 fill its ram with a single color
-    pub fn fill_bw_screen_internal(&mut self, color: bool) -> Result<(), Error<S, R, D, B>> {
-        self.wait_for_busy()?;
+    pub async fn fill_bw_screen_internal(&mut self, color: bool) -> Result<(), Error<S, R, D, B>> {
+        self.auto_write(RamPlane::BlackWhite, AutoWritePattern::solid(color))
+            .await
+    }
+
+    /// Let the ssd1680 fill its red ram with a single color
+    pub async fn fill_red_screen_internal(&mut self, color: bool) -> Result<(), Error<S, R, D, B>> {
+        self.auto_write(RamPlane::Red, AutoWritePattern::solid(color))
+            .await
+    }
+
+    /// Clean image retention by cycling the panel through black and white full refreshes, then display `image`.
+    ///
+    /// The black and white frames are generated by the controller (auto write RAM), only `image` is sent over SPI.
+    /// When `invert` is true, each of these frames is refreshed a second time with the black/white RAM inverted.
+    pub async fn deep_clean(
+        &mut self,
+        cycles: u8,
+        invert: bool,
+        image: &[u8],
+    ) -> Result<(), Error<S, R, D, B>> {
+        for _ in 0..cycles {
+            for color in [false, true] {
+                self.fill_bw_screen_internal(color).await?;
+                self.full_refresh().await?;
+
+                if invert {
+                    self.display_update_control_1(
+                        self.config.red_ram_content_for_display_update,
+                        UpdateRamOption::Inverse,
+                        self.config.s8_source_output_mode,
+                    )
+                    .await?;
+                    self.full_refresh().await?;
+                    self.apply_ram_content_options().await?;
+                }
+            }
+        }
 
-        self.write_command(SsdCommand::AutoWriteBWRam)?;
-        // TODO: different height and width, here its 296x128
-        let data = ((color as u8) << 7) | 0x60 | 0x04;
-        self.write_data(&[data])?;
-        self.wait_for_busy()?;
+        self.show_frame(image, RefreshKind::Full, true).await?;
         Ok(())
     }
 
     /// Manually fill the memory with a single color
-    pub fn fill_bw_screen(&mut self, color: bool) -> Result<(), Error<S, R, D, B>> {
-        self.wait_for_busy()?;
+    pub async fn fill_bw_screen(&mut self, color: bool) -> Result<(), Error<S, R, D, B>> {
+        self.wait_for_busy().await?;
 
-        self.write_command(SsdCommand::WriteRamBW)?;
-        for _ in 0..(self.config.width * self.config.height / 8) {
-            self.write_data(&[(color as u8) * 255])?;
-        }
-        self.write_command(SsdCommand::Nop)?;
-        self.wait_for_busy()?;
+        let count = self.config.width.div_ceil(8) as usize * self.config.height as usize;
+        self.write_ram_repeat(RamPlane::BlackWhite, (color as u8) * 255, count)
+            .await?;
+        self.write_command(SsdCommand::Nop).await?;
+        self.wait_for_busy().await?;
 
         Ok(())
     }
 
     /// Enter deep sleep mode, it is recommended to enter deep sleep after drawing to the screen.
     /// The datasheet indicates that keeping the chip running can deteriorate the display faster.
-    pub fn enter_deep_sleep(&mut self) -> Result<(), Error<S, R, D, B>> {
-        self.write_command(SsdCommand::DeepSleepMode)?;
-        self.write_data(&[0x01])?;
+    pub async fn enter_deep_sleep(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::DeepSleepMode).await?;
+        self.write_data(&[0x01]).await?;
         Ok(())
     }
 
+    /// Start the CRC calculation of the controller (command 0x34), read the result with `read_crc`.
+    ///
+    /// The datasheet describes this command for the validation of the OTP content, it does not document which
+    /// memory is covered: do not compare the result with the CRC of a frame, use `verify_ram` instead.
+    pub async fn start_crc_calculation(&mut self) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::CrcCalculation).await?;
+        self.wait_for_busy().await
+    }
+
+    /// Read the CRC computed by the last `start_crc_calculation` (command 0x35).
+    pub async fn read_crc(&mut self) -> Result<u16, Error<S, R, D, B>> {
+        self.write_command(SsdCommand::CrcStatusRead).await?;
+        let mut buf = [0u8; 2];
+        self.read_data(&mut buf).await?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    /// Check that a RAM plane holds `expected_frame` (a whole screen in the layout of a [`FrameBuffer`],
+    /// `width.div_ceil(8) * height` bytes) by reading it back.
+    pub async fn verify_ram(
+        &mut self,
+        plane: RamPlane,
+        expected_frame: &[u8],
+    ) -> Result<bool, Error<S, R, D, B>> {
+        let stride = self.config.width.div_ceil(8) as usize;
+        if expected_frame.len() != stride * self.config.height as usize {
+            return Ok(false);
+        }
+        let mut buffer = [0u8; STREAM_CHUNK_SIZE];
+        for (y, row) in expected_frame.chunks(stride).enumerate() {
+            for (i, expected) in row.chunks(STREAM_CHUNK_SIZE).enumerate() {
+                let bytes = &mut buffer[..expected.len()];
+                let x = (i * STREAM_CHUNK_SIZE * 8) as u16;
+                self.read_ram_region(plane, x, y as u16, bytes.len() as u16 * 8, 1, bytes)
+                    .await?;
+                if bytes != expected {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
     /// Read from the chip's RAM
-    pub fn read_ram(&mut self) -> Result<u8, Error<S, R, D, B>> {
-        self.wait_for_busy()?;
+    pub async fn read_ram(&mut self) -> Result<u8, Error<S, R, D, B>> {
+        self.wait_for_busy().await?;
 
-        self.write_command(SsdCommand::ReadRam)?;
+        self.write_command(SsdCommand::ReadRam).await?;
         // from the documentation : "first byte is dummy data"
         let mut buf = [0u8; 2];
-        self.spi.read(&mut buf).map_err(Error::SpiError)?;
+        self.read_data(&mut buf).await?;
 
         Ok(buf[1])
     }
+
+    /// Select the RAM read by the `ReadRam` command.
+    pub async fn select_read_ram(&mut self, plane: RamPlane) -> Result<(), Error<S, R, D, B>> {
+        self.write_command(SsdCommand::ReadRamOption).await?;
+        self.write_data(&[(plane == RamPlane::Red) as u8]).await?;
+        Ok(())
+    }
+
+    /// Read a region of one of the RAM planes into `buf`, in the same layout as written.
+    ///
+    /// `x` and `width` are in pixels, `x` must be a multiple of 8, `buf` holds `width.div_ceil(8) * height` bytes.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn read_ram_region(
+        &mut self,
+        plane: RamPlane,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        buf: &mut [u8],
+    ) -> Result<(), Error<S, R, D, B>> {
+        if is_empty_region(width, height) {
+            return Ok(());
+        }
+        self.select_read_ram(plane).await?;
+        self.set_ram_window(x, y, width, height).await?;
+
+        self.write_command(SsdCommand::ReadRam).await?;
+        // the first byte is dummy data
+        self.interface.read_data(true, buf).await?;
+        self.interface.release().await?;
+
+        self.reset_ram_window().await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    use embedded_hal::{
+        delay::DelayNs,
+        digital::{ErrorType, InputPin, OutputPin},
+    };
+
+    use super::SSD1680;
+    use crate::{
+        commands::SsdCommand,
+        config::{DisplayConfig, PartialRefreshMode, RamPlane},
+        error::Error,
+        framebuffer::FrameBuffer,
+        interface::{Interface, InterfaceError},
+        refresh_policy::RefreshKind,
+    };
+
+    /// Interface recording the commands sent, each with its data.
+    #[derive(Default)]
+    struct Recorder {
+        commands: Vec<(u8, Vec<u8>)>,
+        /// `send_data` calls for RAM writes
+        transfers: usize,
+    }
+
+    impl Recorder {
+        /// RAM windows (x start/end in bytes, y start/end) of the writes to the black/white RAM.
+        fn bw_windows(&self) -> Vec<[u16; 4]> {
+            let mut window = [0; 4];
+            let mut windows = Vec::new();
+            for (command, data) in &self.commands {
+                match *command {
+                    0x44 => window[..2].copy_from_slice(&[data[0] as u16, data[1] as u16]),
+                    0x45 => window[2..].copy_from_slice(&[
+                        u16::from_le_bytes([data[0], data[1]]),
+                        u16::from_le_bytes([data[2], data[3]]),
+                    ]),
+                    0x24 => windows.push(window),
+                    _ => {}
+                }
+            }
+            windows
+        }
+
+        /// Commands sent after the last one of `command`.
+        fn after_last(&self, command: SsdCommand) -> &[(u8, Vec<u8>)] {
+            let start = self
+                .commands
+                .iter()
+                .rposition(|(c, _)| *c == command as u8)
+                .map_or(0, |i| i + 1);
+            &self.commands[start..]
+        }
+    }
+
+    impl Interface for Recorder {
+        type BusError = Infallible;
+        type PinError = Infallible;
+
+        fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<Infallible, Infallible>> {
+            self.commands.push((command, Vec::new()));
+            Ok(())
+        }
+
+        fn send_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Infallible, Infallible>> {
+            let (command, bytes) = self.commands.last_mut().unwrap();
+            if *command == SsdCommand::WriteRamBW as u8 || *command == SsdCommand::WriteRamRed as u8
+            {
+                self.transfers += 1;
+            }
+            bytes.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn read_data(
+            &mut self,
+            _skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<Infallible, Infallible>> {
+            buf.fill(0);
+            Ok(())
+        }
+    }
+
+    /// RST pin, and BUSY pin always low.
+    pub(crate) struct Pin;
+
+    impl ErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    impl InputPin for Pin {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            Ok(false)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(true)
+        }
+    }
+
+    pub(crate) struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    /// 64 x 16 display, initialized and showing a first frame with a full refresh.
+    fn display(mode: PartialRefreshMode) -> SSD1680<Pin, Pin, NoDelay, Recorder> {
+        let config = DisplayConfig::default()
+            .with_width(64)
+            .with_height(16)
+            .with_partial_refresh_mode(mode);
+        let mut display = SSD1680::with_interface(Pin, Pin, NoDelay, Recorder::default(), config);
+        display.hw_init().unwrap();
+        display
+            .show_frame(&[0xFF; 128], RefreshKind::Full, false)
+            .unwrap();
+        display
+    }
+
+    #[test]
+    fn window_covers_partial_last_byte() {
+        // 2.13" panel, 122 pixels wide: rows of 16 bytes
+        let config = DisplayConfig::default().with_width(122).with_height(4);
+        let mut display = SSD1680::with_interface(Pin, Pin, NoDelay, Recorder::default(), config);
+        display.hw_init().unwrap();
+        display
+            .show_frame(&[0xFF; 64], RefreshKind::Full, false)
+            .unwrap();
+        assert_eq!(display.interface().bw_windows(), [[0, 15, 0, 3]]);
+    }
+
+    #[test]
+    fn bands_use_whole_byte_rows() {
+        let config = DisplayConfig::default().with_width(122).with_height(10);
+        let mut display = SSD1680::with_interface(Pin, Pin, NoDelay, Recorder::default(), config);
+        display.hw_init().unwrap();
+
+        let mut short = [0u8; 15];
+        let result = display.draw_in_bands(RamPlane::BlackWhite, &mut short, |_| {});
+        assert!(matches!(result, Err(Error::BufferTooSmall)));
+
+        // 4 rows of 16 bytes per strip
+        let mut buffer = [0u8; 70];
+        display.interface_mut().commands.clear();
+        display
+            .draw_in_bands(RamPlane::BlackWhite, &mut buffer, |band| {
+                band.set_pixel(121, 9, false)
+            })
+            .unwrap();
+        let recorder = display.interface();
+        assert_eq!(
+            recorder.bw_windows(),
+            [[0, 15, 0, 3], [0, 15, 4, 7], [0, 15, 8, 9]]
+        );
+        let (_, last_strip) = recorder
+            .commands
+            .iter()
+            .rfind(|(c, _)| *c == SsdCommand::WriteRamBW as u8)
+            .unwrap();
+        assert_eq!(last_strip.len(), 32);
+        assert_eq!(last_strip[31], 0xBF);
+    }
+
+    #[test]
+    fn verify_ram_takes_frame_buffer_layout() {
+        // the recorder reads zeros, a black frame
+        let config = DisplayConfig::default().with_width(122).with_height(2);
+        let mut display = SSD1680::with_interface(Pin, Pin, NoDelay, Recorder::default(), config);
+        display.hw_init().unwrap();
+        let framebuffer = FrameBuffer::<32>::new(122, 2);
+        let black = [0u8; 32];
+        assert!(
+            !display
+                .verify_ram(RamPlane::BlackWhite, framebuffer.as_bytes())
+                .unwrap()
+        );
+        assert!(display.verify_ram(RamPlane::BlackWhite, &black).unwrap());
+    }
+
+    #[test]
+    fn full_width_region_sent_in_one_transfer() {
+        let mut display = display(PartialRefreshMode::Standalone);
+        let mut framebuffer = FrameBuffer::<128>::new(64, 16);
+        *display.interface_mut() = Recorder::default();
+        display
+            .show_framebuffer(&mut framebuffer, RefreshKind::Full, true)
+            .unwrap();
+        let recorder = display.interface();
+        assert_eq!(recorder.bw_windows(), [[0, 7, 0, 15]]);
+        assert_eq!(recorder.transfers, 1);
+    }
+
+    #[test]
+    fn ping_pong_sends_previous_regions_again() {
+        let mut display = display(PartialRefreshMode::PingPong);
+        let mut framebuffer = FrameBuffer::<128>::new(64, 16);
+        framebuffer.clear_dirty();
+
+        // digit A changes
+        framebuffer.set_pixel(0, 0, false);
+        display.interface_mut().commands.clear();
+        display
+            .show_framebuffer(&mut framebuffer, RefreshKind::Partial, false)
+            .unwrap();
+        assert_eq!(display.interface().bw_windows(), [[0, 0, 0, 0]]);
+
+        // then digit B: the RAM written holds the frame before A, A is sent again
+        framebuffer.set_pixel(40, 8, false);
+        display.interface_mut().commands.clear();
+        display
+            .show_framebuffer(&mut framebuffer, RefreshKind::Partial, false)
+            .unwrap();
+        let windows = display.interface().bw_windows();
+        assert_eq!(windows.len(), 2);
+        assert!(windows.contains(&[0, 0, 0, 0]) && windows.contains(&[5, 5, 8, 8]));
+
+        // a full refresh leaves both planes with the frame, nothing is pending after it
+        framebuffer.set_pixel(8, 0, false);
+        display
+            .show_framebuffer(&mut framebuffer, RefreshKind::Full, false)
+            .unwrap();
+        framebuffer.set_pixel(16, 0, false);
+        display.interface_mut().commands.clear();
+        display
+            .show_framebuffer(&mut framebuffer, RefreshKind::Partial, false)
+            .unwrap();
+        assert_eq!(display.interface().bw_windows(), [[2, 2, 0, 0]]);
+    }
+
+    #[test]
+    fn ping_pong_region_written_again_after_update() {
+        let mut display = display(PartialRefreshMode::PingPong);
+
+        display
+            .show_region(0, 0, 8, 1, &[0x0F], RefreshKind::Partial, false)
+            .unwrap();
+        let after_update = display.interface().after_last(SsdCommand::MasterActivation);
+        assert!(after_update.contains(&(SsdCommand::WriteRamBW as u8, [0x0F].into())));
+        assert!(
+            !after_update
+                .iter()
+                .any(|(c, _)| *c == SsdCommand::WriteRamRed as u8)
+        );
+
+        display
+            .show_region(40, 8, 8, 1, &[0xF0], RefreshKind::Partial, false)
+            .unwrap();
+        assert_eq!(display.interface().bw_windows().last(), Some(&[5, 5, 8, 8]));
+    }
 }
@@ -3,5 +3,14 @@ pub enum Error<S, R, D, B> {
     SpiError(S),
     RstPinError(R),
     DcPinError(D),
+    /// CS pin driven by the interface (`SpiBusInterface`), it has the error type of the DC pin.
+    CsPinError(D),
     BusyPinError(B),
+    /// A refresh started with `start_refresh` is still running, wait for it with `finish_refresh` or `poll`.
+    RefreshInProgress,
+    /// A wait on BUSY was cancelled (async future dropped), the controller state is unknown: call `abort_refresh`
+    /// or `hw_init`.
+    NeedsReinit,
+    /// The buffer given to `draw_in_bands` does not hold a row of the screen.
+    BufferTooSmall,
 }
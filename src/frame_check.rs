@@ -0,0 +1,303 @@
+use crate::{commands::SsdCommand, config::RamPlane};
+
+/// Result of an update that can be skipped when the frame did not change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateStatus {
+    /// The data was sent (and displayed, for refreshes).
+    Updated,
+    /// Same content and window as the last time, nothing was sent.
+    Unchanged,
+}
+
+/// FNV-1a checksum, used to recognize a frame already written to the RAM.
+#[derive(Clone, Copy)]
+pub struct Checksum(u32);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checksum {
+    pub const fn new() -> Self {
+        Checksum(0x811C_9DC5)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u32;
+            self.0 = self.0.wrapping_mul(0x0100_0193);
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+
+    pub fn of(bytes: &[u8]) -> u32 {
+        let mut checksum = Self::new();
+        checksum.update(bytes);
+        checksum.value()
+    }
+}
+
+/// Where a write lands in the RAM: window (x start/end in bytes, y start/end) and counters at the start of the write.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RamPosition {
+    pub window: [u16; 4],
+    pub counter: [u16; 2],
+}
+
+impl RamPosition {
+    /// Position used to write a region, `x` and `width` in pixels.
+    pub fn region(x: u16, y: u16, width: u16, height: u16) -> Self {
+        RamPosition {
+            window: [
+                x / 8,
+                (x + width).div_ceil(8).saturating_sub(1),
+                y,
+                (y + height).saturating_sub(1),
+            ],
+            counter: [x / 8, y],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PlaneWrite {
+    position: RamPosition,
+    checksum: u32,
+}
+
+/// Mirror of what the driver knows about the RAM content, to skip writes and refreshes that would change nothing.
+pub(crate) struct RamTracker {
+    window: [Option<u16>; 4],
+    counter: [Option<u16>; 2],
+    bw: Option<PlaneWrite>,
+    red: Option<PlaneWrite>,
+    changed_since_refresh: bool,
+    /// The red RAM does not hold the displayed frame (after a reset), partial refreshes against it would ghost
+    reference_stale: bool,
+}
+
+impl RamTracker {
+    pub const fn new() -> Self {
+        RamTracker {
+            window: [None; 4],
+            counter: [None; 2],
+            bw: None,
+            red: None,
+            changed_since_refresh: true,
+            reference_stale: true,
+        }
+    }
+
+    pub fn set_window_x(&mut self, start: u16, end: u16) {
+        self.window[0] = Some(start);
+        self.window[1] = Some(end);
+    }
+
+    pub fn set_window_y(&mut self, start: u16, end: u16) {
+        self.window[2] = Some(start);
+        self.window[3] = Some(end);
+    }
+
+    pub fn set_counter_x(&mut self, x: u16) {
+        self.counter[0] = Some(x);
+    }
+
+    pub fn set_counter_y(&mut self, y: u16) {
+        self.counter[1] = Some(y);
+    }
+
+    /// Position the next write will land at, if known.
+    pub fn position(&self) -> Option<RamPosition> {
+        let [Some(xs), Some(xe), Some(ys), Some(ye)] = self.window else {
+            return None;
+        };
+        let [Some(x), Some(y)] = self.counter else {
+            return None;
+        };
+        Some(RamPosition {
+            window: [xs, xe, ys, ye],
+            counter: [x, y],
+        })
+    }
+
+    fn plane(&mut self, plane: RamPlane) -> &mut Option<PlaneWrite> {
+        match plane {
+            RamPlane::BlackWhite => &mut self.bw,
+            RamPlane::Red => &mut self.red,
+        }
+    }
+
+    /// Update the knowledge of the RAM before a command is sent.
+    pub fn on_command(&mut self, command: SsdCommand, ping_pong: bool) {
+        match command {
+            SsdCommand::WriteRamBW | SsdCommand::AutoWriteBWRam => {
+                self.forget(RamPlane::BlackWhite);
+            }
+            SsdCommand::WriteRamRed | SsdCommand::AutoWriteRedRam => {
+                self.forget(RamPlane::Red);
+            }
+            SsdCommand::SWReset | SsdCommand::DeepSleepMode => {
+                *self = Self::new();
+            }
+            SsdCommand::DisplayUpdateControl1
+            | SsdCommand::WriteLutRegister
+            | SsdCommand::BorderWaveformnControl
+            | SsdCommand::WriteRegisterDisplayOption => {
+                // same RAM, but shown differently at the next refresh
+                self.changed_since_refresh = true;
+            }
+            SsdCommand::MasterActivation => {
+                self.changed_since_refresh = false;
+                if ping_pong {
+                    // the controller may swap the planes
+                    self.bw = None;
+                    self.red = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn forget(&mut self, plane: RamPlane) {
+        *self.plane(plane) = None;
+        self.counter = [None; 2];
+        self.changed_since_refresh = true;
+    }
+
+    /// True if the plane already holds `checksum` at `position`.
+    pub fn is_written(&self, plane: RamPlane, position: RamPosition, checksum: u32) -> bool {
+        let write = match plane {
+            RamPlane::BlackWhite => self.bw,
+            RamPlane::Red => self.red,
+        };
+        write.is_some_and(|w| w.position == position && w.checksum == checksum)
+    }
+
+    /// True if the plane already holds `checksum` at `position` and it was displayed since.
+    pub fn is_displayed(&self, plane: RamPlane, position: RamPosition, checksum: u32) -> bool {
+        !self.changed_since_refresh && self.is_written(plane, position, checksum)
+    }
+
+    /// The RAM changes since the last refresh are already on the screen, like the previous frame copied to the red RAM.
+    pub fn mark_displayed(&mut self) {
+        self.changed_since_refresh = false;
+        self.reference_stale = false;
+    }
+
+    /// True until the displayed frame is written to the red RAM after a reset.
+    pub fn is_reference_stale(&self) -> bool {
+        self.reference_stale
+    }
+
+    pub fn has_changed_since_refresh(&self) -> bool {
+        self.changed_since_refresh
+    }
+
+    /// Record a write done after the RAM write command.
+    pub fn record_write(&mut self, plane: RamPlane, position: Option<RamPosition>, checksum: u32) {
+        *self.plane(plane) = position.map(|position| PlaneWrite { position, checksum });
+        self.counter = [None; 2];
+        self.changed_since_refresh = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RamPosition, RamTracker};
+    use crate::{commands::SsdCommand, config::RamPlane};
+
+    const POSITION: RamPosition = RamPosition {
+        window: [0, 7, 0, 15],
+        counter: [0, 0],
+    };
+
+    /// Tracker with `checksum` written at `POSITION` of both planes and displayed.
+    fn displayed(checksum: u32) -> RamTracker {
+        let mut ram = RamTracker::new();
+        ram.record_write(RamPlane::BlackWhite, Some(POSITION), checksum);
+        ram.record_write(RamPlane::Red, Some(POSITION), checksum);
+        ram.on_command(SsdCommand::MasterActivation, false);
+        ram
+    }
+
+    #[test]
+    fn displayed_after_refresh() {
+        let mut ram = RamTracker::new();
+        ram.record_write(RamPlane::BlackWhite, Some(POSITION), 1);
+        assert!(ram.is_written(RamPlane::BlackWhite, POSITION, 1));
+        assert!(!ram.is_displayed(RamPlane::BlackWhite, POSITION, 1));
+
+        ram.on_command(SsdCommand::MasterActivation, false);
+        assert!(ram.is_displayed(RamPlane::BlackWhite, POSITION, 1));
+        assert!(!ram.is_displayed(RamPlane::BlackWhite, POSITION, 2));
+        assert!(!ram.is_displayed(RamPlane::BlackWhite, RamPosition::region(8, 0, 56, 16), 1));
+        assert!(!ram.is_displayed(RamPlane::Red, POSITION, 1));
+    }
+
+    #[test]
+    fn changed_by_display_options() {
+        let mut ram = displayed(1);
+        ram.on_command(SsdCommand::DisplayUpdateControl1, false);
+        assert!(ram.has_changed_since_refresh());
+        assert!(!ram.is_displayed(RamPlane::BlackWhite, POSITION, 1));
+        assert!(ram.is_written(RamPlane::BlackWhite, POSITION, 1));
+    }
+
+    #[test]
+    fn write_commands_forget_their_plane() {
+        for (command, forgotten, kept) in [
+            (SsdCommand::WriteRamBW, RamPlane::BlackWhite, RamPlane::Red),
+            (
+                SsdCommand::AutoWriteBWRam,
+                RamPlane::BlackWhite,
+                RamPlane::Red,
+            ),
+            (SsdCommand::WriteRamRed, RamPlane::Red, RamPlane::BlackWhite),
+            (
+                SsdCommand::AutoWriteRedRam,
+                RamPlane::Red,
+                RamPlane::BlackWhite,
+            ),
+        ] {
+            let mut ram = displayed(1);
+            ram.on_command(command, false);
+            assert!(!ram.is_written(forgotten, POSITION, 1));
+            assert!(ram.is_written(kept, POSITION, 1));
+            assert!(ram.has_changed_since_refresh());
+        }
+    }
+
+    #[test]
+    fn ping_pong_update_forgets_both_planes() {
+        let mut ram = displayed(1);
+        ram.on_command(SsdCommand::MasterActivation, true);
+        assert!(!ram.is_written(RamPlane::BlackWhite, POSITION, 1));
+        assert!(!ram.is_written(RamPlane::Red, POSITION, 1));
+    }
+
+    #[test]
+    fn reset_forgets_everything() {
+        for command in [SsdCommand::SWReset, SsdCommand::DeepSleepMode] {
+            let mut ram = displayed(1);
+            ram.set_window_x(0, 7);
+            ram.set_window_y(0, 15);
+            ram.set_counter_x(0);
+            ram.set_counter_y(0);
+            ram.mark_displayed();
+            assert!(ram.position().is_some());
+            assert!(!ram.is_reference_stale());
+
+            ram.on_command(command, false);
+            assert!(ram.position().is_none());
+            assert!(!ram.is_written(RamPlane::BlackWhite, POSITION, 1));
+            assert!(!ram.is_written(RamPlane::Red, POSITION, 1));
+            assert!(ram.has_changed_since_refresh());
+            assert!(ram.is_reference_stale());
+        }
+    }
+}
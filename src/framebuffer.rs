@@ -0,0 +1,216 @@
+use crate::dirty::{DirtyRegions, Rect};
+
+/// Black and white frame buffer, laid out like the controller RAM:
+/// one bit per pixel (1 is white, 0 is black), rows of `width.div_ceil(8)` bytes, most significant bit first.
+///
+/// `N` is the size of the buffer in bytes, `width.div_ceil(8) * height`.
+/// Changes are tracked as dirty regions, so only those are sent to the display.
+pub struct FrameBuffer<const N: usize> {
+    data: [u8; N],
+    width: u16,
+    height: u16,
+    dirty: DirtyRegions,
+}
+
+impl<const N: usize> FrameBuffer<N> {
+    /// Create a white frame buffer, marked as fully dirty.
+    pub fn new(width: u16, height: u16) -> Self {
+        assert_eq!(
+            N,
+            width.div_ceil(8) as usize * height as usize,
+            "frame buffer size does not match the dimensions"
+        );
+        let mut buffer = FrameBuffer {
+            data: [0xFF; N],
+            width,
+            height,
+            dirty: DirtyRegions::new(),
+        };
+        buffer.mark_all_dirty();
+        buffer
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Bytes per row.
+    pub fn stride(&self) -> usize {
+        self.width.div_ceil(8) as usize
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Bytes of one row of a byte-aligned rectangle.
+    pub fn row_bytes(&self, rect: &Rect, y: u16) -> &[u8] {
+        let start = y as usize * self.stride() + rect.x as usize / 8;
+        &self.data[start..start + rect.width as usize / 8]
+    }
+
+    /// Get a pixel, true is white.
+    pub fn pixel(&self, x: u16, y: u16) -> Option<bool> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = y as usize * self.stride() + x as usize / 8;
+        Some(self.data[index] & (0x80 >> (x % 8)) != 0)
+    }
+
+    /// Set a pixel, true is white. Pixels outside of the buffer are ignored.
+    pub fn set_pixel(&mut self, x: u16, y: u16, white: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = y as usize * self.stride() + x as usize / 8;
+        let mask = 0x80 >> (x % 8);
+        let byte = if white {
+            self.data[index] | mask
+        } else {
+            self.data[index] & !mask
+        };
+        if byte != self.data[index] {
+            self.data[index] = byte;
+            self.dirty.add(Rect::new(x, y, 1, 1));
+        }
+    }
+
+    /// Fill the whole buffer with a single color, true is white.
+    pub fn fill(&mut self, white: bool) {
+        self.data = [if white { 0xFF } else { 0x00 }; N];
+        self.mark_all_dirty();
+    }
+
+    /// Regions changed since the last flush.
+    pub fn dirty(&self) -> &DirtyRegions {
+        &self.dirty
+    }
+
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        self.dirty.add(rect);
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.clear();
+        self.dirty.add(Rect::new(0, 0, self.width, self.height));
+    }
+
+    /// Forget the changes, called once they are sent to the display.
+    pub fn clear_dirty(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// Copy the content of another frame buffer of the same dimensions, and add its dirty regions to the ones of this buffer.
+    pub fn copy_from(&mut self, other: &FrameBuffer<N>) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "frame buffer dimensions do not match"
+        );
+        self.data.copy_from_slice(&other.data);
+        for rect in other.dirty.rects() {
+            self.dirty.add(*rect);
+        }
+    }
+
+    /// Replace the dirty regions with the bytes that differ from `previous`, the last frame flushed.
+    pub fn diff_with(&mut self, previous: &[u8]) {
+        self.dirty.clear();
+        let stride = self.stride();
+        for (y, (row, previous_row)) in self
+            .data
+            .chunks(stride)
+            .zip(previous.chunks(stride))
+            .enumerate()
+        {
+            let first = row.iter().zip(previous_row).position(|(a, b)| a != b);
+            let last = row.iter().zip(previous_row).rposition(|(a, b)| a != b);
+            if let (Some(first), Some(last)) = (first, last) {
+                self.dirty.add(Rect::new(
+                    first as u16 * 8,
+                    y as u16,
+                    (last - first + 1) as u16 * 8,
+                    1,
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "graphics")]
+mod graphics {
+    use super::FrameBuffer;
+    use embedded_graphics_core::{
+        Pixel,
+        draw_target::DrawTarget,
+        geometry::{OriginDimensions, Size},
+        pixelcolor::BinaryColor,
+    };
+
+    /// `BinaryColor::On` is black, `BinaryColor::Off` is white.
+    impl<const N: usize> DrawTarget for FrameBuffer<N> {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                if let (Ok(x), Ok(y)) = (u16::try_from(point.x), u16::try_from(point.y)) {
+                    self.set_pixel(x, y, color.is_off());
+                }
+            }
+            Ok(())
+        }
+
+        fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+            self.fill(color.is_off());
+            Ok(())
+        }
+    }
+
+    impl<const N: usize> OriginDimensions for FrameBuffer<N> {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, self.height as u32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameBuffer;
+    use crate::dirty::Rect;
+
+    #[test]
+    fn diff_gives_byte_aligned_rows() {
+        // rows far enough apart not to be merged
+        let mut framebuffer = FrameBuffer::<320>::new(64, 40);
+        let previous = *framebuffer.as_bytes().first_chunk::<320>().unwrap();
+        framebuffer.set_pixel(9, 1, false);
+        framebuffer.set_pixel(3, 30, false);
+        framebuffer.set_pixel(42, 30, false);
+        framebuffer.diff_with(&previous);
+
+        for rect in framebuffer.dirty().rects() {
+            assert!(rect.x.is_multiple_of(8) && rect.width.is_multiple_of(8));
+            assert_eq!(rect.height, 1);
+        }
+        assert_eq!(
+            framebuffer.dirty().rects(),
+            &[Rect::new(8, 1, 8, 1), Rect::new(0, 30, 48, 1)]
+        );
+    }
+
+    #[test]
+    fn diff_of_same_frame_is_empty() {
+        let mut framebuffer = FrameBuffer::<12>::new(32, 3);
+        let previous = *framebuffer.as_bytes().first_chunk::<12>().unwrap();
+        framebuffer.diff_with(&previous);
+        assert!(framebuffer.dirty().is_empty());
+    }
+}
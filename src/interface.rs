@@ -0,0 +1,721 @@
+use embedded_hal::{
+    digital::OutputPin,
+    spi::{Operation, SpiBus, SpiDevice},
+};
+
+use crate::error::Error;
+
+/// Error of an interface: either the SPI bus or one of the pins driven by the interface.
+#[derive(Debug)]
+pub enum InterfaceError<S, P> {
+    Spi(S),
+    /// DC pin, or the pins of an interface without DC
+    Pin(P),
+    /// CS pin driven by the interface
+    Cs(P),
+}
+
+impl<S, R, D, B> From<InterfaceError<S, D>> for Error<S, R, D, B> {
+    fn from(error: InterfaceError<S, D>) -> Self {
+        match error {
+            InterfaceError::Spi(e) => Error::SpiError(e),
+            InterfaceError::Pin(e) => Error::DcPinError(e),
+            InterfaceError::Cs(e) => Error::CsPinError(e),
+        }
+    }
+}
+
+/// How commands and data reach the controller.
+///
+/// The driver only talks to the controller through this trait, so the 4-wire SPI transports of this module can be
+/// replaced by any other one (3-wire, shared bus, a recorder for tests, ...) without changing drawing or refresh code.
+///
+/// The driver calls `send_command` once per command, then `send_data` or `read_data` any number of times for its
+/// parameters, RAM data or answer. `release` is called once they are sent and before every wait on the BUSY pin, a
+/// transport holding the bus across a command frees it there. Errors reach the user as `Error::SpiError` for
+/// `BusError`, and `Error::DcPinError` (`InterfaceError::Pin`) or `Error::CsPinError` (`InterfaceError::Cs`) for
+/// `PinError`.
+pub trait Interface {
+    type BusError;
+    type PinError;
+
+    /// Send a command byte.
+    fn send_command(
+        &mut self,
+        command: u8,
+    ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>>;
+
+    /// Send parameters or RAM data of the last command, may be called several times per command.
+    fn send_data(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>>;
+
+    /// Read data answered to the last command, the dummy byte sent first by the controller is skipped if `skip_dummy`.
+    fn read_data(
+        &mut self,
+        skip_dummy: bool,
+        buf: &mut [u8],
+    ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>>;
+
+    /// The command and its data are sent, also called before waiting on the BUSY pin.
+    fn release(&mut self) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+        Ok(())
+    }
+}
+
+/// Lets the caller keep the interface, for example to inspect a test transport between two driver calls.
+impl<T: Interface + ?Sized> Interface for &mut T {
+    type BusError = T::BusError;
+    type PinError = T::PinError;
+
+    fn send_command(
+        &mut self,
+        command: u8,
+    ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+        T::send_command(self, command)
+    }
+
+    fn send_data(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+        T::send_data(self, data)
+    }
+
+    fn read_data(
+        &mut self,
+        skip_dummy: bool,
+        buf: &mut [u8],
+    ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+        T::read_data(self, skip_dummy, buf)
+    }
+
+    fn release(&mut self) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+        T::release(self)
+    }
+}
+
+/// 4-wire interface on a `SpiDevice` (CS handled by the device, released after each transfer) and a DC pin.
+///
+/// Reads go through `read_spi` when set, as the controller supports a lower clock speed for reads than for writes.
+pub struct SpiInterface<SPI, DC, RSPI = SPI> {
+    spi: SPI,
+    dc: DC,
+    read_spi: Option<RSPI>,
+    /// Last level set on the DC pin, None when unknown
+    dc_high: Option<bool>,
+}
+
+impl<SPI, DC> SpiInterface<SPI, DC> {
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        SpiInterface {
+            spi,
+            dc,
+            read_spi: None,
+            dc_high: None,
+        }
+    }
+
+    /// Use a separate SPI device for reads, usually the same bus configured with a lower clock speed.
+    pub fn with_read_spi<RS>(self, read_spi: RS) -> SpiInterface<SPI, DC, RS> {
+        SpiInterface {
+            spi: self.spi,
+            dc: self.dc,
+            read_spi: Some(read_spi),
+            dc_high: self.dc_high,
+        }
+    }
+}
+
+impl<SPI, DC, RSPI> SpiInterface<SPI, DC, RSPI> {
+    pub fn release_parts(self) -> (SPI, DC, Option<RSPI>) {
+        (self.spi, self.dc, self.read_spi)
+    }
+}
+
+/// Set the DC pin, unless it is already at this level.
+fn set_dc<DC: OutputPin>(
+    dc: &mut DC,
+    dc_high: &mut Option<bool>,
+    high: bool,
+) -> Result<(), DC::Error> {
+    if *dc_high != Some(high) {
+        *dc_high = None;
+        if high {
+            dc.set_high()?;
+        } else {
+            dc.set_low()?;
+        }
+        *dc_high = Some(high);
+    }
+    Ok(())
+}
+
+impl<SPI, DC, RSPI> Interface for SpiInterface<SPI, DC, RSPI>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    RSPI: SpiDevice<Error = SPI::Error>,
+{
+    type BusError = SPI::Error;
+    type PinError = DC::Error;
+
+    fn send_command(&mut self, command: u8) -> Result<(), InterfaceError<SPI::Error, DC::Error>> {
+        set_dc(&mut self.dc, &mut self.dc_high, false).map_err(InterfaceError::Pin)?;
+        self.spi.write(&[command]).map_err(InterfaceError::Spi)
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<SPI::Error, DC::Error>> {
+        set_dc(&mut self.dc, &mut self.dc_high, true).map_err(InterfaceError::Pin)?;
+        self.spi.write(data).map_err(InterfaceError::Spi)
+    }
+
+    fn read_data(
+        &mut self,
+        skip_dummy: bool,
+        buf: &mut [u8],
+    ) -> Result<(), InterfaceError<SPI::Error, DC::Error>> {
+        set_dc(&mut self.dc, &mut self.dc_high, true).map_err(InterfaceError::Pin)?;
+        let mut dummy = [0u8; 1];
+        let operations = &mut [
+            Operation::Read(&mut dummy[..skip_dummy as usize]),
+            Operation::Read(buf),
+        ];
+        match self.read_spi.as_mut() {
+            Some(read_spi) => read_spi.transaction(operations),
+            None => self.spi.transaction(operations),
+        }
+        .map_err(InterfaceError::Spi)
+    }
+}
+
+/// 4-wire interface on a `SpiBus` with a CS pin and a DC pin.
+///
+/// CS stays asserted from a command to the end of its parameters or RAM data, and is released by `release`, called by
+/// the driver at the end of each command: other devices of the bus can be used between two driver calls.
+///
+/// CS and DC must have the same error type, their failures are reported as `Error::CsPinError` and
+/// `Error::DcPinError`.
+pub struct SpiBusInterface<BUS, CS, DC> {
+    bus: BUS,
+    cs: CS,
+    dc: DC,
+    cs_active: bool,
+    dc_high: Option<bool>,
+}
+
+impl<BUS, CS, DC> SpiBusInterface<BUS, CS, DC> {
+    pub fn new(bus: BUS, cs: CS, dc: DC) -> Self {
+        SpiBusInterface {
+            bus,
+            cs,
+            dc,
+            cs_active: false,
+            dc_high: None,
+        }
+    }
+
+    pub fn release_parts(self) -> (BUS, CS, DC) {
+        (self.bus, self.cs, self.dc)
+    }
+}
+
+impl<BUS, CS: OutputPin, DC> SpiBusInterface<BUS, CS, DC> {
+    /// Assert CS, if not already done for the current command.
+    fn begin_transfer(&mut self) -> Result<(), CS::Error> {
+        if !self.cs_active {
+            self.cs.set_low()?;
+            self.cs_active = true;
+        }
+        Ok(())
+    }
+}
+
+impl<BUS, CS, DC> SpiBusInterface<BUS, CS, DC>
+where
+    BUS: SpiBus,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+{
+    /// Wait for the end of the transfers, then set DC (both are needed before the controller samples DC).
+    fn set_dc(&mut self, high: bool) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+        if self.dc_high != Some(high) {
+            self.bus.flush().map_err(InterfaceError::Spi)?;
+            set_dc(&mut self.dc, &mut self.dc_high, high).map_err(InterfaceError::Pin)?;
+        }
+        Ok(())
+    }
+
+    fn end_transfer(&mut self) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+        if self.cs_active {
+            self.bus.flush().map_err(InterfaceError::Spi)?;
+            self.cs.set_high().map_err(InterfaceError::Cs)?;
+            self.cs_active = false;
+        }
+        Ok(())
+    }
+}
+
+impl<BUS, CS, DC> Interface for SpiBusInterface<BUS, CS, DC>
+where
+    BUS: SpiBus,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+{
+    type BusError = BUS::Error;
+    type PinError = DC::Error;
+
+    fn send_command(&mut self, command: u8) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+        self.end_transfer()?;
+        self.set_dc(false)?;
+        self.begin_transfer().map_err(InterfaceError::Cs)?;
+        self.bus.write(&[command]).map_err(InterfaceError::Spi)
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+        self.set_dc(true)?;
+        self.begin_transfer().map_err(InterfaceError::Cs)?;
+        self.bus.write(data).map_err(InterfaceError::Spi)
+    }
+
+    fn read_data(
+        &mut self,
+        skip_dummy: bool,
+        buf: &mut [u8],
+    ) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+        self.set_dc(true)?;
+        self.begin_transfer().map_err(InterfaceError::Cs)?;
+        if skip_dummy {
+            self.bus.read(&mut [0u8; 1]).map_err(InterfaceError::Spi)?;
+        }
+        self.bus.read(buf).map_err(InterfaceError::Spi)?;
+        self.bus.flush().map_err(InterfaceError::Spi)
+    }
+
+    fn release(&mut self) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+        self.end_transfer()
+    }
+}
+
+#[cfg(feature = "async")]
+pub use asynch::AsyncInterface;
+
+#[cfg(feature = "display-interface")]
+pub use display_interface_adapter::DisplayInterfaceAdapter;
+
+#[cfg(feature = "display-interface")]
+mod display_interface_adapter {
+    use core::convert::Infallible;
+
+    use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+
+    use super::{Interface, InterfaceError};
+
+    /// Interface on any `display-interface` implementation (SPI, parallel, ...).
+    ///
+    /// Errors are reported as `Error::SpiError`. The interface is write-only: reads, used by `read_ram`, the CRC and
+    /// the direct draw target, fail with `DisplayError::DataFormatNotImplemented`.
+    pub struct DisplayInterfaceAdapter<DI> {
+        di: DI,
+    }
+
+    impl<DI> DisplayInterfaceAdapter<DI> {
+        pub fn new(di: DI) -> Self {
+            DisplayInterfaceAdapter { di }
+        }
+
+        pub fn release_parts(self) -> DI {
+            self.di
+        }
+    }
+
+    impl<DI: WriteOnlyDataCommand> Interface for DisplayInterfaceAdapter<DI> {
+        type BusError = DisplayError;
+        type PinError = Infallible;
+
+        fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<DisplayError, Infallible>> {
+            self.di
+                .send_commands(DataFormat::U8(&[command]))
+                .map_err(InterfaceError::Spi)
+        }
+
+        fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<DisplayError, Infallible>> {
+            self.di
+                .send_data(DataFormat::U8(data))
+                .map_err(InterfaceError::Spi)
+        }
+
+        fn read_data(
+            &mut self,
+            _skip_dummy: bool,
+            _buf: &mut [u8],
+        ) -> Result<(), InterfaceError<DisplayError, Infallible>> {
+            Err(InterfaceError::Spi(DisplayError::DataFormatNotImplemented))
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl<DI: display_interface::AsyncWriteOnlyDataCommand> super::AsyncInterface
+        for DisplayInterfaceAdapter<DI>
+    {
+        type BusError = DisplayError;
+        type PinError = Infallible;
+
+        async fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<DisplayError, Infallible>> {
+            self.di
+                .send_commands(DataFormat::U8(&[command]))
+                .await
+                .map_err(InterfaceError::Spi)
+        }
+
+        async fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<DisplayError, Infallible>> {
+            self.di
+                .send_data(DataFormat::U8(data))
+                .await
+                .map_err(InterfaceError::Spi)
+        }
+
+        async fn read_data(
+            &mut self,
+            _skip_dummy: bool,
+            _buf: &mut [u8],
+        ) -> Result<(), InterfaceError<DisplayError, Infallible>> {
+            Err(InterfaceError::Spi(DisplayError::DataFormatNotImplemented))
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+mod asynch {
+    use embedded_hal::digital::OutputPin;
+    use embedded_hal_async::spi::{Operation, SpiBus, SpiDevice};
+
+    use super::{InterfaceError, SpiBusInterface, SpiInterface, set_dc};
+
+    /// How commands and data reach the controller, for the async driver. See [`Interface`](super::Interface).
+    #[allow(async_fn_in_trait)]
+    pub trait AsyncInterface {
+        type BusError;
+        type PinError;
+
+        /// Send a command byte.
+        async fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>>;
+
+        /// Send parameters or RAM data of the last command, may be called several times per command.
+        async fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>>;
+
+        /// Read data answered to the last command, the dummy byte sent first by the controller is skipped if `skip_dummy`.
+        async fn read_data(
+            &mut self,
+            skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>>;
+
+        /// The command and its data are sent, also called before waiting on the BUSY pin.
+        async fn release(&mut self) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+            Ok(())
+        }
+    }
+
+    impl<T: AsyncInterface + ?Sized> AsyncInterface for &mut T {
+        type BusError = T::BusError;
+        type PinError = T::PinError;
+
+        async fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+            T::send_command(self, command).await
+        }
+
+        async fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+            T::send_data(self, data).await
+        }
+
+        async fn read_data(
+            &mut self,
+            skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+            T::read_data(self, skip_dummy, buf).await
+        }
+
+        async fn release(&mut self) -> Result<(), InterfaceError<Self::BusError, Self::PinError>> {
+            T::release(self).await
+        }
+    }
+
+    impl<SPI, DC, RSPI> AsyncInterface for SpiInterface<SPI, DC, RSPI>
+    where
+        SPI: SpiDevice,
+        DC: OutputPin,
+        RSPI: SpiDevice<Error = SPI::Error>,
+    {
+        type BusError = SPI::Error;
+        type PinError = DC::Error;
+
+        async fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<SPI::Error, DC::Error>> {
+            set_dc(&mut self.dc, &mut self.dc_high, false).map_err(InterfaceError::Pin)?;
+            self.spi
+                .write(&[command])
+                .await
+                .map_err(InterfaceError::Spi)
+        }
+
+        async fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<SPI::Error, DC::Error>> {
+            set_dc(&mut self.dc, &mut self.dc_high, true).map_err(InterfaceError::Pin)?;
+            self.spi.write(data).await.map_err(InterfaceError::Spi)
+        }
+
+        async fn read_data(
+            &mut self,
+            skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<SPI::Error, DC::Error>> {
+            set_dc(&mut self.dc, &mut self.dc_high, true).map_err(InterfaceError::Pin)?;
+            let mut dummy = [0u8; 1];
+            let operations = &mut [
+                Operation::Read(&mut dummy[..skip_dummy as usize]),
+                Operation::Read(buf),
+            ];
+            match self.read_spi.as_mut() {
+                Some(read_spi) => read_spi.transaction(operations).await,
+                None => self.spi.transaction(operations).await,
+            }
+            .map_err(InterfaceError::Spi)
+        }
+    }
+
+    impl<BUS, CS, DC> SpiBusInterface<BUS, CS, DC>
+    where
+        BUS: SpiBus,
+        CS: OutputPin,
+        DC: OutputPin<Error = CS::Error>,
+    {
+        async fn set_dc_async(
+            &mut self,
+            high: bool,
+        ) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+            if self.dc_high != Some(high) {
+                self.bus.flush().await.map_err(InterfaceError::Spi)?;
+                set_dc(&mut self.dc, &mut self.dc_high, high).map_err(InterfaceError::Pin)?;
+            }
+            Ok(())
+        }
+
+        async fn end_transfer_async(
+            &mut self,
+        ) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+            if self.cs_active {
+                self.bus.flush().await.map_err(InterfaceError::Spi)?;
+                self.cs.set_high().map_err(InterfaceError::Cs)?;
+                self.cs_active = false;
+            }
+            Ok(())
+        }
+    }
+
+    impl<BUS, CS, DC> AsyncInterface for SpiBusInterface<BUS, CS, DC>
+    where
+        BUS: SpiBus,
+        CS: OutputPin,
+        DC: OutputPin<Error = CS::Error>,
+    {
+        type BusError = BUS::Error;
+        type PinError = DC::Error;
+
+        async fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+            self.end_transfer_async().await?;
+            self.set_dc_async(false).await?;
+            self.begin_transfer().map_err(InterfaceError::Cs)?;
+            self.bus
+                .write(&[command])
+                .await
+                .map_err(InterfaceError::Spi)
+        }
+
+        async fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+            self.set_dc_async(true).await?;
+            self.begin_transfer().map_err(InterfaceError::Cs)?;
+            self.bus.write(data).await.map_err(InterfaceError::Spi)
+        }
+
+        async fn read_data(
+            &mut self,
+            skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+            self.set_dc_async(true).await?;
+            self.begin_transfer().map_err(InterfaceError::Cs)?;
+            if skip_dummy {
+                self.bus
+                    .read(&mut [0u8; 1])
+                    .await
+                    .map_err(InterfaceError::Spi)?;
+            }
+            self.bus.read(buf).await.map_err(InterfaceError::Spi)?;
+            self.bus.flush().await.map_err(InterfaceError::Spi)
+        }
+
+        async fn release(&mut self) -> Result<(), InterfaceError<BUS::Error, DC::Error>> {
+            self.end_transfer_async().await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{cell::RefCell, convert::Infallible};
+    use std::vec::Vec;
+
+    use embedded_hal::{
+        digital::{ErrorType, OutputPin},
+        spi::SpiBus,
+    };
+
+    use super::SpiBusInterface;
+    use crate::{
+        SSD1680,
+        config::DisplayConfig,
+        driver::tests::{NoDelay, Pin},
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Cs(bool),
+        Dc(bool),
+        Write(Vec<u8>),
+    }
+
+    struct Bus<'a>(&'a RefCell<Vec<Event>>);
+
+    impl embedded_hal::spi::ErrorType for Bus<'_> {
+        type Error = Infallible;
+    }
+
+    impl SpiBus for Bus<'_> {
+        fn read(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+            words.fill(0);
+            Ok(())
+        }
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(Event::Write(words.into()));
+            Ok(())
+        }
+
+        fn transfer(&mut self, read: &mut [u8], _write: &[u8]) -> Result<(), Infallible> {
+            read.fill(0);
+            Ok(())
+        }
+
+        fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+            words.fill(0);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    /// CS or DC pin, recording its levels.
+    struct LogPin<'a> {
+        log: &'a RefCell<Vec<Event>>,
+        event: fn(bool) -> Event,
+    }
+
+    impl ErrorType for LogPin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for LogPin<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.log.borrow_mut().push((self.event)(false));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.log.borrow_mut().push((self.event)(true));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn bus_released_after_each_command() {
+        let log = RefCell::new(Vec::new());
+        let interface = SpiBusInterface::new(
+            Bus(&log),
+            LogPin {
+                log: &log,
+                event: Event::Cs,
+            },
+            LogPin {
+                log: &log,
+                event: Event::Dc,
+            },
+        );
+        let mut display =
+            SSD1680::with_interface(Pin, Pin, NoDelay, interface, DisplayConfig::default());
+
+        display.set_ram_counter_y(0x123).unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Event::Dc(false),
+                Event::Cs(false),
+                Event::Write([0x4F].into()),
+                Event::Dc(true),
+                Event::Write([0x23, 0x01].into()),
+                Event::Cs(true),
+            ]
+        );
+
+        display.sw_reset().unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Event::Dc(false),
+                Event::Cs(false),
+                Event::Write([0x12].into()),
+                Event::Cs(true),
+            ]
+        );
+    }
+}
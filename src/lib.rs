@@ -1,8 +1,21 @@
 #![no_std]
+pub mod band;
+pub mod canvas;
 pub mod commands;
 pub mod config;
+#[cfg(feature = "graphics")]
+pub mod direct_draw;
+pub mod dirty;
+pub mod display;
 pub mod driver;
 #[cfg(feature = "async")]
 pub mod driver_async;
 pub mod error;
+pub mod frame_check;
+pub mod framebuffer;
+pub mod interface;
+pub mod refresh_policy;
+#[cfg(feature = "embassy")]
+pub mod service;
+pub mod three_wire;
 pub use driver::*;
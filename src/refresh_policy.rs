@@ -0,0 +1,216 @@
+/// Source of time used to decide when a full refresh is due.
+pub trait Clock {
+    /// Current time in milliseconds, only the difference between two readings is used.
+    fn now_ms(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now_ms(&self) -> u64 {
+        self()
+    }
+}
+
+/// Kind of refresh that was (or will be) sent to the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshKind {
+    Partial,
+    Full,
+}
+
+/// When to escalate a partial refresh to a full refresh, to limit ghosting.
+///
+/// Every condition is optional, a full refresh is done as soon as one of them is met.
+#[derive(Clone, Copy)]
+pub struct RefreshPolicy {
+    /// Maximum number of partial refreshes between two full refreshes.
+    pub max_partial_refreshes: Option<u32>,
+    /// Maximum time between two full refreshes, in milliseconds.
+    pub max_time_between_full_ms: Option<u64>,
+    /// Fraction of the screen (0.0 to 1.0) above which a change is shown with a full refresh.
+    pub max_changed_fraction: Option<f32>,
+}
+
+/// Full refresh every 10 partial refreshes or when more than half of the screen changed.
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        Self {
+            max_partial_refreshes: Some(10),
+            max_time_between_full_ms: None,
+            max_changed_fraction: Some(0.5),
+        }
+    }
+}
+
+impl RefreshPolicy {
+    /// Never escalate on its own, only the first refresh is a full one.
+    pub fn never() -> Self {
+        Self {
+            max_partial_refreshes: None,
+            max_time_between_full_ms: None,
+            max_changed_fraction: None,
+        }
+    }
+
+    pub fn with_max_partial_refreshes(mut self, count: Option<u32>) -> Self {
+        self.max_partial_refreshes = count;
+        self
+    }
+
+    pub fn with_max_time_between_full_ms(mut self, time: Option<u64>) -> Self {
+        self.max_time_between_full_ms = time;
+        self
+    }
+
+    pub fn with_max_changed_fraction(mut self, fraction: Option<f32>) -> Self {
+        self.max_changed_fraction = fraction;
+        self
+    }
+}
+
+/// Keeps track of the refreshes done to apply a [`RefreshPolicy`].
+///
+/// Pass it to `SSD1680::refresh` (blocking or async), which picks the kind of refresh for you.
+/// The first refresh is always a full one.
+pub struct RefreshManager<C: Clock> {
+    policy: RefreshPolicy,
+    clock: C,
+    partial_count: u32,
+    last_full_ms: Option<u64>,
+}
+
+impl<C: Clock> RefreshManager<C> {
+    pub fn new(policy: RefreshPolicy, clock: C) -> Self {
+        RefreshManager {
+            policy,
+            clock,
+            partial_count: 0,
+            last_full_ms: None,
+        }
+    }
+
+    pub fn policy(&self) -> &RefreshPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: RefreshPolicy) {
+        self.policy = policy;
+    }
+
+    /// Number of partial refreshes since the last full refresh.
+    pub fn partial_count(&self) -> u32 {
+        self.partial_count
+    }
+
+    /// Make the next refresh a full one, for example after waking the display from deep sleep.
+    pub fn force_full(&mut self) {
+        self.last_full_ms = None;
+    }
+
+    /// Decide which refresh to use, `changed_area` and `total_area` are in pixels.
+    pub fn next_refresh(&self, changed_area: u32, total_area: u32) -> RefreshKind {
+        let Some(last_full_ms) = self.last_full_ms else {
+            return RefreshKind::Full;
+        };
+
+        if let Some(max) = self.policy.max_partial_refreshes
+            && self.partial_count >= max
+        {
+            return RefreshKind::Full;
+        }
+
+        if let Some(max) = self.policy.max_time_between_full_ms
+            && self.clock.now_ms().wrapping_sub(last_full_ms) >= max
+        {
+            return RefreshKind::Full;
+        }
+
+        if let Some(max) = self.policy.max_changed_fraction
+            && total_area > 0
+            && changed_area as f32 > max * total_area as f32
+        {
+            return RefreshKind::Full;
+        }
+
+        RefreshKind::Partial
+    }
+
+    /// Record a refresh, use it when refreshing the display without going through the manager.
+    pub fn record(&mut self, kind: RefreshKind) {
+        match kind {
+            RefreshKind::Partial => self.partial_count = self.partial_count.saturating_add(1),
+            RefreshKind::Full => {
+                self.partial_count = 0;
+                self.last_full_ms = Some(self.clock.now_ms());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::{RefreshKind, RefreshManager, RefreshPolicy};
+
+    #[test]
+    fn first_refresh_is_full() {
+        let mut manager = RefreshManager::new(RefreshPolicy::never(), || 0);
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Full);
+        manager.record(RefreshKind::Full);
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Partial);
+    }
+
+    #[test]
+    fn escalates_after_partial_count() {
+        let policy = RefreshPolicy::never().with_max_partial_refreshes(Some(2));
+        let mut manager = RefreshManager::new(policy, || 0);
+        manager.record(RefreshKind::Full);
+        for _ in 0..2 {
+            assert_eq!(manager.next_refresh(1, 100), RefreshKind::Partial);
+            manager.record(RefreshKind::Partial);
+        }
+        assert_eq!(manager.partial_count(), 2);
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Full);
+
+        manager.record(RefreshKind::Full);
+        assert_eq!(manager.partial_count(), 0);
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Partial);
+    }
+
+    #[test]
+    fn escalates_after_time_across_wrap() {
+        let now = Cell::new(u64::MAX - 100);
+        let policy = RefreshPolicy::never().with_max_time_between_full_ms(Some(1000));
+        let mut manager = RefreshManager::new(policy, || now.get());
+        manager.record(RefreshKind::Full);
+
+        now.set(now.get().wrapping_add(999));
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Partial);
+        now.set(now.get().wrapping_add(1));
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Full);
+
+        manager.record(RefreshKind::Full);
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Partial);
+    }
+
+    #[test]
+    fn escalates_above_changed_fraction() {
+        let policy = RefreshPolicy::never().with_max_changed_fraction(Some(0.5));
+        let mut manager = RefreshManager::new(policy, || 0);
+        manager.record(RefreshKind::Full);
+        assert_eq!(manager.next_refresh(50, 100), RefreshKind::Partial);
+        assert_eq!(manager.next_refresh(51, 100), RefreshKind::Full);
+        assert_eq!(manager.next_refresh(51, 0), RefreshKind::Partial);
+    }
+
+    #[test]
+    fn force_full() {
+        let mut manager = RefreshManager::new(RefreshPolicy::never(), || 0);
+        manager.record(RefreshKind::Full);
+        manager.record(RefreshKind::Partial);
+        manager.force_full();
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Full);
+        manager.record(RefreshKind::Full);
+        assert_eq!(manager.next_refresh(1, 100), RefreshKind::Partial);
+    }
+}
@@ -0,0 +1,210 @@
+//! Async display service, sharing one display between several embassy tasks.
+//!
+//! Tasks draw into a shared [`FrameBuffer`] and request refreshes through a [`DisplayHandle`].
+//! The [`DisplayService`] task owns the driver and serves the requests: the requests sent while a refresh is
+//! running are merged into a single follow-up refresh, of the highest requested priority.
+
+use core::cell::Cell;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex as BlockingMutex, raw::RawMutex},
+    mutex::Mutex,
+    signal::Signal,
+};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::{
+    driver::{AsyncBusyPin, SSD1680Async},
+    error::Error,
+    framebuffer::FrameBuffer,
+    interface::AsyncInterface,
+    refresh_policy::{Clock, RefreshKind, RefreshManager},
+};
+
+/// Refresh asked by a task, ordered by priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RefreshRequest {
+    /// Show the changes, the [`RefreshManager`] chooses between a partial and a full refresh.
+    Partial,
+    /// Show the whole frame with a full refresh.
+    Full,
+}
+
+/// State shared between the service and its handles: the frame buffer and the pending request.
+///
+/// It is usually kept in a `static`, `N` is the size of the frame buffer (see [`FrameBuffer`]).
+pub struct DisplayState<M: RawMutex, const N: usize> {
+    framebuffer: Mutex<M, FrameBuffer<N>>,
+    pending: BlockingMutex<M, Cell<Option<RefreshRequest>>>,
+    wake: Signal<M, ()>,
+    width: u16,
+    height: u16,
+}
+
+impl<M: RawMutex, const N: usize> DisplayState<M, N> {
+    pub fn new(width: u16, height: u16) -> Self {
+        DisplayState {
+            framebuffer: Mutex::new(FrameBuffer::new(width, height)),
+            pending: BlockingMutex::new(Cell::new(None)),
+            wake: Signal::new(),
+            width,
+            height,
+        }
+    }
+
+    /// Merge a request with the pending one and wake the service.
+    fn request(&self, request: RefreshRequest) {
+        self.pending
+            .lock(|pending| pending.set(pending.get().max(Some(request))));
+        self.wake.signal(());
+    }
+
+    fn take_request(&self) -> Option<RefreshRequest> {
+        self.pending.lock(|pending| pending.take())
+    }
+}
+
+/// Cloneable access to the display for the tasks.
+pub struct DisplayHandle<'a, M: RawMutex, const N: usize> {
+    state: &'a DisplayState<M, N>,
+}
+
+impl<M: RawMutex, const N: usize> Clone for DisplayHandle<'_, M, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: RawMutex, const N: usize> Copy for DisplayHandle<'_, M, N> {}
+
+impl<'a, M: RawMutex, const N: usize> DisplayHandle<'a, M, N> {
+    pub fn new(state: &'a DisplayState<M, N>) -> Self {
+        DisplayHandle { state }
+    }
+
+    /// Draw into the frame buffer without refreshing the display.
+    ///
+    /// The frame buffer is only locked while the service copies it, not during the refresh.
+    pub async fn draw<T>(&self, draw: impl FnOnce(&mut FrameBuffer<N>) -> T) -> T {
+        let mut framebuffer = self.state.framebuffer.lock().await;
+        draw(&mut framebuffer)
+    }
+
+    /// Ask for a refresh, merged with the requests not served yet.
+    pub fn request(&self, request: RefreshRequest) {
+        self.state.request(request);
+    }
+
+    /// Draw into the frame buffer, then ask for a refresh.
+    pub async fn update<T>(
+        &self,
+        request: RefreshRequest,
+        draw: impl FnOnce(&mut FrameBuffer<N>) -> T,
+    ) -> T {
+        let result = self.draw(draw).await;
+        self.request(request);
+        result
+    }
+}
+
+/// Task owning the async driver and serving the refresh requests of the [`DisplayHandle`]s.
+///
+/// The frame buffer is copied before each refresh, so tasks can keep drawing while the panel is busy.
+pub struct DisplayService<'a, M: RawMutex, const N: usize, RST, BUSY, DELAY, DI, C>
+where
+    RST: OutputPin,
+    BUSY: AsyncBusyPin,
+    DELAY: DelayNs,
+    DI: AsyncInterface,
+    C: Clock,
+{
+    display: SSD1680Async<RST, BUSY, DELAY, DI>,
+    state: &'a DisplayState<M, N>,
+    manager: RefreshManager<C>,
+    frame: FrameBuffer<N>,
+}
+
+impl<'a, M, const N: usize, RST, BUSY, DELAY, DI, C, S, R, D, B>
+    DisplayService<'a, M, N, RST, BUSY, DELAY, DI, C>
+where
+    M: RawMutex,
+    RST: OutputPin<Error = R>,
+    BUSY: AsyncBusyPin<Error = B>,
+    DELAY: DelayNs,
+    DI: AsyncInterface<BusError = S, PinError = D>,
+    C: Clock,
+{
+    /// Create the service, the display must already be initialized with `hw_init`.
+    ///
+    /// Panics if the frame buffer of `state` does not have the dimensions of the display.
+    pub fn new(
+        display: SSD1680Async<RST, BUSY, DELAY, DI>,
+        state: &'a DisplayState<M, N>,
+        manager: RefreshManager<C>,
+    ) -> Self {
+        let config = display.config();
+        assert!(
+            state.width == config.width && state.height == config.height,
+            "frame buffer dimensions do not match the display"
+        );
+        DisplayService {
+            frame: FrameBuffer::new(config.width, config.height),
+            display,
+            state,
+            manager,
+        }
+    }
+
+    pub fn handle(&self) -> DisplayHandle<'a, M, N> {
+        DisplayHandle::new(self.state)
+    }
+
+    pub fn display(&mut self) -> &mut SSD1680Async<RST, BUSY, DELAY, DI> {
+        &mut self.display
+    }
+
+    pub fn manager(&mut self) -> &mut RefreshManager<C> {
+        &mut self.manager
+    }
+
+    pub fn release(self) -> SSD1680Async<RST, BUSY, DELAY, DI> {
+        self.display
+    }
+
+    /// Wait for a request and serve it, with all the requests received meanwhile.
+    ///
+    /// Returns the kind of refresh done, `None` if the frame buffer content was already displayed.
+    pub async fn process(&mut self) -> Result<Option<RefreshKind>, Error<S, R, D, B>> {
+        let request = loop {
+            if let Some(request) = self.state.take_request() {
+                break request;
+            }
+            self.state.wake.wait().await;
+        };
+
+        {
+            let mut framebuffer = self.state.framebuffer.lock().await;
+            self.frame.copy_from(&framebuffer);
+            framebuffer.clear_dirty();
+        }
+
+        let force = request == RefreshRequest::Full;
+        if force {
+            self.manager.force_full();
+            self.frame.mark_all_dirty();
+        }
+        self.display
+            .refresh_framebuffer(&mut self.manager, &mut self.frame, force)
+            .await
+    }
+
+    /// Serve the requests until the driver fails.
+    pub async fn run(&mut self) -> Error<S, R, D, B> {
+        loop {
+            if let Err(error) = self.process().await {
+                return error;
+            }
+        }
+    }
+}
@@ -0,0 +1,622 @@
+use core::convert::Infallible;
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    spi::{Operation, SpiDevice},
+};
+
+use crate::interface::{Interface, InterfaceError};
+
+/// Data words sent per SPI transfer, 64 words of 9 bits fill exactly 72 bytes.
+const WORDS_PER_TRANSFER: usize = 64;
+const PACKED_SIZE: usize = WORDS_PER_TRANSFER * 9 / 8;
+
+/// 9-bit words (D/C bit, then the byte) packed most significant bit first into bytes.
+struct Packer {
+    buf: [u8; PACKED_SIZE],
+    bits: usize,
+}
+
+impl Packer {
+    fn new() -> Self {
+        Packer {
+            buf: [0; PACKED_SIZE],
+            bits: 0,
+        }
+    }
+
+    fn push(&mut self, data: bool, byte: u8) {
+        let word = ((data as u16) << 8) | byte as u16;
+        for i in (0..9).rev() {
+            if word >> i & 1 != 0 {
+                self.buf[self.bits / 8] |= 0x80 >> (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    /// Packed bytes, the last one padded with zeros. The controller drops the incomplete word when CS goes high.
+    fn bytes(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.bits.div_ceil(8)]
+    }
+}
+
+/// Realign bytes read one bit off: the 7 low bits of `first` are the start of the data, each byte of `buf` holds
+/// the last bit of the previous data byte followed by 7 bits of the next one.
+fn shift_read(first: u8, buf: &mut [u8]) {
+    let mut carry = first & 0x7F;
+    for byte in buf {
+        let read = *byte;
+        *byte = (carry << 1) | (read >> 7);
+        carry = read & 0x7F;
+    }
+}
+
+/// 3-wire interface (BS1 strapped high): a D/C bit in front of each byte replaces the DC pin.
+///
+/// Works with any 8-bit `SpiDevice`, words are packed into bytes and the padding bits are dropped by the controller
+/// when CS goes high. The controller answers a read command in the same transfer, so reads send the last command
+/// again. MISO and MOSI are both wired to SDA, MOSI through a resistor so that the controller can drive the line.
+pub struct ThreeWireSpiInterface<SPI> {
+    spi: SPI,
+    last_command: Option<u8>,
+}
+
+impl<SPI> ThreeWireSpiInterface<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        ThreeWireSpiInterface {
+            spi,
+            last_command: None,
+        }
+    }
+
+    pub fn release_parts(self) -> SPI {
+        self.spi
+    }
+
+    /// Header of a read: the command, then ones while the controller sends the dummy byte if skipped.
+    /// The read data starts at the second bit of the last byte of the header.
+    fn read_header(command: u8, skip_dummy: bool) -> ([u8; 3], usize) {
+        let mut packer = Packer::new();
+        packer.push(false, command);
+        let packed = packer.bytes();
+        let header = [packed[0], packed[1] | 0x7F, 0xFF];
+        (header, 2 + skip_dummy as usize)
+    }
+}
+
+impl<SPI: SpiDevice> Interface for ThreeWireSpiInterface<SPI> {
+    type BusError = SPI::Error;
+    type PinError = Infallible;
+
+    fn send_command(&mut self, command: u8) -> Result<(), InterfaceError<SPI::Error, Infallible>> {
+        self.last_command = Some(command);
+        let mut packer = Packer::new();
+        packer.push(false, command);
+        self.spi.write(packer.bytes()).map_err(InterfaceError::Spi)
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<SPI::Error, Infallible>> {
+        for chunk in data.chunks(WORDS_PER_TRANSFER) {
+            let mut packer = Packer::new();
+            for byte in chunk {
+                packer.push(true, *byte);
+            }
+            self.spi
+                .write(packer.bytes())
+                .map_err(InterfaceError::Spi)?;
+        }
+        Ok(())
+    }
+
+    fn read_data(
+        &mut self,
+        skip_dummy: bool,
+        buf: &mut [u8],
+    ) -> Result<(), InterfaceError<SPI::Error, Infallible>> {
+        let Some(command) = self.last_command else {
+            let mut dummy = [0u8; 1];
+            return self
+                .spi
+                .transaction(&mut [
+                    Operation::Read(&mut dummy[..skip_dummy as usize]),
+                    Operation::Read(buf),
+                ])
+                .map_err(InterfaceError::Spi);
+        };
+        let (mut header, len) = Self::read_header(command, skip_dummy);
+        self.spi
+            .transaction(&mut [
+                Operation::TransferInPlace(&mut header[..len]),
+                Operation::Read(buf),
+            ])
+            .map_err(InterfaceError::Spi)?;
+        shift_read(header[len - 1], buf);
+        Ok(())
+    }
+}
+
+/// Bit-banged 3-wire interface: SCL, SDA and CS driven by GPIOs, with exact 9-bit words.
+///
+/// SDA must be an open-drain pin with a pull-up (or a pin switching direction on its own), it is released high
+/// while reading. CS stays asserted from a command to the end of its parameters, RAM data or answer, and is released
+/// by `release`, called by the driver at the end of each command.
+pub struct BitBangInterface<SCL, SDA, CS, DELAY> {
+    scl: SCL,
+    sda: SDA,
+    cs: CS,
+    delay: DELAY,
+    half_period_ns: u32,
+    cs_active: bool,
+}
+
+impl<SCL, SDA, CS, DELAY> BitBangInterface<SCL, SDA, CS, DELAY> {
+    /// Create the interface with a 500 ns clock period, slow enough for reads.
+    pub fn new(scl: SCL, sda: SDA, cs: CS, delay: DELAY) -> Self {
+        BitBangInterface {
+            scl,
+            sda,
+            cs,
+            delay,
+            half_period_ns: 250,
+            cs_active: false,
+        }
+    }
+
+    pub fn with_half_period_ns(mut self, half_period_ns: u32) -> Self {
+        self.half_period_ns = half_period_ns;
+        self
+    }
+
+    pub fn release_parts(self) -> (SCL, SDA, CS, DELAY) {
+        (self.scl, self.sda, self.cs, self.delay)
+    }
+}
+
+impl<SCL, SDA, CS, DELAY, P> BitBangInterface<SCL, SDA, CS, DELAY>
+where
+    SCL: OutputPin<Error = P>,
+    SDA: OutputPin<Error = P> + InputPin<Error = P>,
+    CS: OutputPin<Error = P>,
+    DELAY: DelayNs,
+{
+    fn begin_transfer(&mut self) -> Result<(), P> {
+        if !self.cs_active {
+            self.scl.set_low()?;
+            self.cs.set_low()?;
+            self.cs_active = true;
+        }
+        Ok(())
+    }
+
+    fn end_transfer(&mut self) -> Result<(), P> {
+        if self.cs_active {
+            self.cs.set_high()?;
+            self.cs_active = false;
+        }
+        Ok(())
+    }
+
+    /// Send one bit, sampled by the controller on the rising edge of SCL.
+    fn write_bit(&mut self, bit: bool) -> Result<(), P> {
+        if bit {
+            self.sda.set_high()?;
+        } else {
+            self.sda.set_low()?;
+        }
+        self.delay.delay_ns(self.half_period_ns);
+        self.scl.set_high()?;
+        self.delay.delay_ns(self.half_period_ns);
+        self.scl.set_low()
+    }
+
+    fn read_bit(&mut self) -> Result<bool, P> {
+        self.delay.delay_ns(self.half_period_ns);
+        self.scl.set_high()?;
+        let bit = self.sda.is_high()?;
+        self.delay.delay_ns(self.half_period_ns);
+        self.scl.set_low()?;
+        Ok(bit)
+    }
+
+    fn write_word(&mut self, data: bool, byte: u8) -> Result<(), P> {
+        self.write_bit(data)?;
+        for i in (0..8).rev() {
+            self.write_bit(byte >> i & 1 != 0)?;
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<u8, P> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = (byte << 1) | self.read_bit()? as u8;
+        }
+        Ok(byte)
+    }
+}
+
+impl<SCL, SDA, CS, DELAY, P> Interface for BitBangInterface<SCL, SDA, CS, DELAY>
+where
+    SCL: OutputPin<Error = P>,
+    SDA: OutputPin<Error = P> + InputPin<Error = P>,
+    CS: OutputPin<Error = P>,
+    DELAY: DelayNs,
+{
+    type BusError = Infallible;
+    type PinError = P;
+
+    fn send_command(&mut self, command: u8) -> Result<(), InterfaceError<Infallible, P>> {
+        self.end_transfer().map_err(InterfaceError::Pin)?;
+        self.begin_transfer().map_err(InterfaceError::Pin)?;
+        self.write_word(false, command).map_err(InterfaceError::Pin)
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Infallible, P>> {
+        self.begin_transfer().map_err(InterfaceError::Pin)?;
+        for byte in data {
+            self.write_word(true, *byte).map_err(InterfaceError::Pin)?;
+        }
+        Ok(())
+    }
+
+    fn read_data(
+        &mut self,
+        skip_dummy: bool,
+        buf: &mut [u8],
+    ) -> Result<(), InterfaceError<Infallible, P>> {
+        self.begin_transfer().map_err(InterfaceError::Pin)?;
+        self.sda.set_high().map_err(InterfaceError::Pin)?;
+        if skip_dummy {
+            self.read_byte().map_err(InterfaceError::Pin)?;
+        }
+        for byte in buf {
+            *byte = self.read_byte().map_err(InterfaceError::Pin)?;
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<(), InterfaceError<Infallible, P>> {
+        self.end_transfer().map_err(InterfaceError::Pin)
+    }
+}
+
+#[cfg(feature = "async")]
+mod asynch {
+    use core::convert::Infallible;
+
+    use embedded_hal::{
+        delay::DelayNs,
+        digital::{InputPin, OutputPin},
+    };
+    use embedded_hal_async::spi::{Operation, SpiDevice};
+
+    use super::{BitBangInterface, Packer, ThreeWireSpiInterface, WORDS_PER_TRANSFER, shift_read};
+    use crate::interface::{AsyncInterface, Interface, InterfaceError};
+
+    impl<SPI: SpiDevice> AsyncInterface for ThreeWireSpiInterface<SPI> {
+        type BusError = SPI::Error;
+        type PinError = Infallible;
+
+        async fn send_command(
+            &mut self,
+            command: u8,
+        ) -> Result<(), InterfaceError<SPI::Error, Infallible>> {
+            self.last_command = Some(command);
+            let mut packer = Packer::new();
+            packer.push(false, command);
+            self.spi
+                .write(packer.bytes())
+                .await
+                .map_err(InterfaceError::Spi)
+        }
+
+        async fn send_data(
+            &mut self,
+            data: &[u8],
+        ) -> Result<(), InterfaceError<SPI::Error, Infallible>> {
+            for chunk in data.chunks(WORDS_PER_TRANSFER) {
+                let mut packer = Packer::new();
+                for byte in chunk {
+                    packer.push(true, *byte);
+                }
+                self.spi
+                    .write(packer.bytes())
+                    .await
+                    .map_err(InterfaceError::Spi)?;
+            }
+            Ok(())
+        }
+
+        async fn read_data(
+            &mut self,
+            skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<SPI::Error, Infallible>> {
+            let Some(command) = self.last_command else {
+                let mut dummy = [0u8; 1];
+                return self
+                    .spi
+                    .transaction(&mut [
+                        Operation::Read(&mut dummy[..skip_dummy as usize]),
+                        Operation::Read(buf),
+                    ])
+                    .await
+                    .map_err(InterfaceError::Spi);
+            };
+            let (mut header, len) = Self::read_header(command, skip_dummy);
+            self.spi
+                .transaction(&mut [
+                    Operation::TransferInPlace(&mut header[..len]),
+                    Operation::Read(buf),
+                ])
+                .await
+                .map_err(InterfaceError::Spi)?;
+            shift_read(header[len - 1], buf);
+            Ok(())
+        }
+    }
+
+    /// Bit-banging does not wait on anything, the async driver runs the blocking implementation.
+    impl<SCL, SDA, CS, DELAY, P> AsyncInterface for BitBangInterface<SCL, SDA, CS, DELAY>
+    where
+        SCL: OutputPin<Error = P>,
+        SDA: OutputPin<Error = P> + InputPin<Error = P>,
+        CS: OutputPin<Error = P>,
+        DELAY: DelayNs,
+    {
+        type BusError = Infallible;
+        type PinError = P;
+
+        async fn send_command(&mut self, command: u8) -> Result<(), InterfaceError<Infallible, P>> {
+            Interface::send_command(self, command)
+        }
+
+        async fn send_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Infallible, P>> {
+            Interface::send_data(self, data)
+        }
+
+        async fn read_data(
+            &mut self,
+            skip_dummy: bool,
+            buf: &mut [u8],
+        ) -> Result<(), InterfaceError<Infallible, P>> {
+            Interface::read_data(self, skip_dummy, buf)
+        }
+
+        async fn release(&mut self) -> Result<(), InterfaceError<Infallible, P>> {
+            Interface::release(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{cell::RefCell, convert::Infallible};
+    use std::vec::Vec;
+
+    use embedded_hal::{
+        digital::{self, InputPin, OutputPin},
+        spi::{ErrorType, Operation, SpiDevice},
+    };
+
+    use super::{BitBangInterface, Packer, ThreeWireSpiInterface, shift_read};
+    use crate::{
+        SSD1680,
+        config::DisplayConfig,
+        driver::tests::{NoDelay, Pin},
+        interface::Interface,
+    };
+
+    #[test]
+    fn pack_command() {
+        let mut packer = Packer::new();
+        packer.push(false, 0x12);
+        assert_eq!(packer.bytes(), &[0x09, 0x00]);
+    }
+
+    #[test]
+    fn pack_data() {
+        let mut packer = Packer::new();
+        packer.push(true, 0xA5);
+        assert_eq!(packer.bytes(), &[0xD2, 0x80]);
+
+        let mut packer = Packer::new();
+        packer.push(true, 0xFF);
+        packer.push(true, 0x00);
+        assert_eq!(packer.bytes(), &[0xFF, 0xC0, 0x00]);
+    }
+
+    #[test]
+    fn pack_full_transfer_without_padding() {
+        let mut packer = Packer::new();
+        for _ in 0..8 {
+            packer.push(true, 0x00);
+        }
+        // 8 words of 9 bits: exactly 9 bytes, a one every 9 bits
+        assert_eq!(
+            packer.bytes(),
+            &[0x80, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00]
+        );
+
+        let mut packer = Packer::new();
+        for _ in 0..super::WORDS_PER_TRANSFER {
+            packer.push(true, 0xFF);
+        }
+        assert_eq!(packer.bytes().len(), super::PACKED_SIZE);
+        assert!(packer.bytes().iter().all(|byte| *byte == 0xFF));
+    }
+
+    #[test]
+    fn read_header() {
+        assert_eq!(
+            ThreeWireSpiInterface::<()>::read_header(0x2F, false),
+            ([0x17, 0xFF, 0xFF], 2)
+        );
+        assert_eq!(
+            ThreeWireSpiInterface::<()>::read_header(0x12, true),
+            ([0x09, 0x7F, 0xFF], 3)
+        );
+    }
+
+    #[test]
+    fn realign_read() {
+        // data 0xB4 0x69 read one bit late, after the last bit of the command
+        let mut buf = [0x34, 0xBF];
+        shift_read(0xDA, &mut buf);
+        assert_eq!(buf, [0xB4, 0x69]);
+    }
+
+    /// Controller answering on the shared data line after the 9 bits of the command.
+    struct SimulatedController {
+        response: &'static [u8],
+    }
+
+    impl SimulatedController {
+        /// Level of the data line at bit `index` of a read transaction.
+        fn bit(&self, index: usize, sent: &[u8]) -> u8 {
+            if index < 9 {
+                return sent[index / 8] >> (7 - index % 8) & 1;
+            }
+            let index = index - 9;
+            self.response
+                .get(index / 8)
+                .map_or(1, |byte| byte >> (7 - index % 8) & 1)
+        }
+    }
+
+    impl ErrorType for SimulatedController {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for SimulatedController {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            let mut sent = [0xFFu8; 2];
+            let mut index = 0;
+            for operation in operations {
+                let buf = match operation {
+                    Operation::TransferInPlace(buf) => {
+                        for (sent, byte) in sent.iter_mut().zip(buf.iter()) {
+                            *sent = *byte;
+                        }
+                        buf
+                    }
+                    Operation::Read(buf) => buf,
+                    _ => continue,
+                };
+                for byte in buf.iter_mut() {
+                    *byte = (0..8).fold(0, |byte, _| {
+                        index += 1;
+                        (byte << 1) | self.bit(index - 1, &sent)
+                    });
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_without_dummy() {
+        let mut interface = ThreeWireSpiInterface::new(SimulatedController {
+            response: &[0xB4, 0x69],
+        });
+        interface.send_command(0x2F).unwrap();
+        let mut buf = [0; 2];
+        interface.read_data(false, &mut buf).unwrap();
+        assert_eq!(buf, [0xB4, 0x69]);
+    }
+
+    #[test]
+    fn read_with_dummy() {
+        let mut interface = ThreeWireSpiInterface::new(SimulatedController {
+            response: &[0x5C, 0x01, 0xFE, 0x80],
+        });
+        interface.send_command(0x27).unwrap();
+        let mut buf = [0; 3];
+        interface.read_data(true, &mut buf).unwrap();
+        assert_eq!(buf, [0x01, 0xFE, 0x80]);
+    }
+
+    /// Levels seen by the controller on the bit-banged lines.
+    #[derive(Default)]
+    struct Wire {
+        sda: bool,
+        /// SDA sampled on each rising edge of SCL
+        bits: Vec<bool>,
+        cs: Vec<bool>,
+    }
+
+    enum Line {
+        Scl,
+        Sda,
+        Cs,
+    }
+
+    struct WirePin<'a> {
+        wire: &'a RefCell<Wire>,
+        line: Line,
+    }
+
+    impl WirePin<'_> {
+        fn set(&mut self, high: bool) -> Result<(), Infallible> {
+            let mut wire = self.wire.borrow_mut();
+            match self.line {
+                Line::Scl if high => {
+                    let sda = wire.sda;
+                    wire.bits.push(sda);
+                }
+                Line::Scl => {}
+                Line::Sda => wire.sda = high,
+                Line::Cs => wire.cs.push(high),
+            }
+            Ok(())
+        }
+    }
+
+    impl digital::ErrorType for WirePin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for WirePin<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.set(false)
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.set(true)
+        }
+    }
+
+    impl InputPin for WirePin<'_> {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            Ok(self.wire.borrow().sda)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.wire.borrow().sda)
+        }
+    }
+
+    #[test]
+    fn bit_bang_released_after_each_command() {
+        let wire = RefCell::new(Wire::default());
+        let pin = |line| WirePin { wire: &wire, line };
+        let interface =
+            BitBangInterface::new(pin(Line::Scl), pin(Line::Sda), pin(Line::Cs), NoDelay);
+        let mut display =
+            SSD1680::with_interface(Pin, Pin, NoDelay, interface, DisplayConfig::default());
+
+        display.set_ram_counter_y(0x123).unwrap();
+        let wire = wire.take();
+        let words: Vec<u16> = wire
+            .bits
+            .chunks(9)
+            .map(|word| word.iter().fold(0, |w, bit| (w << 1) | *bit as u16))
+            .collect();
+        assert_eq!(words, [0x04F, 0x123, 0x101]);
+        assert_eq!(wire.cs, [false, true]);
+    }
+}